@@ -0,0 +1,222 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use std::collections::{HashMap, HashSet};
+
+use ::error::{HsmGenError, Result, Diagnostics};
+use ::location::SourceLocation;
+use ::ir::{State, Transition, Event, Label, ModelBuilder, VertexKind, join_code};
+
+
+/// The text of a diagram file, for locating its lines
+pub struct DiagramSource {
+    pub file : String,
+    pub src  : String,
+}
+
+impl DiagramSource {
+    /// Location of the text on line `line` (starting at 0)
+    pub fn location(&self, line: usize, text: &str) -> Option<SourceLocation> {
+        let column = self.src.lines().nth(line).and_then(|x| x.find(text)).unwrap_or(0);
+        Some(SourceLocation::from_line_col(&self.file, &self.src, line + 1, column + 1))
+    }
+
+    pub fn error<S: Into<String>>(&self, line: usize, text: &str, msg: S) -> HsmGenError {
+        HsmGenError::parse(msg).with_location(self.location(line, text))
+    }
+}
+
+/// What is known about a PlantUML or Mermaid state diagram while its lines
+/// are read. The readers parse the lines, this keeps the states they declare.
+pub struct Diagram<'r> {
+    pub source      : &'r DiagramSource,
+    pub diagnostics : &'r Diagnostics,
+    pub builder     : ModelBuilder,
+    /// Ids of the composite states the current line is in
+    pub scopes      : Vec<String>,
+    /// States declared with `state`, states only used in transitions are
+    /// moved into the composite state that declares them later
+    declared        : HashSet<String>,
+    /// The state and first line of a multi line note, and its lines so far
+    pub note        : Option<(Option<String>, usize, Vec<String>)>,
+}
+
+impl<'r> Diagram<'r> {
+    pub fn new(source: &'r DiagramSource, diagnostics: &'r Diagnostics) -> Self {
+        Diagram {
+            source      : source,
+            diagnostics : diagnostics,
+            builder     : ModelBuilder::new(),
+            scopes      : Vec::new(),
+            declared    : HashSet::new(),
+            note        : None,
+        }
+    }
+
+    /// Adds a line to the open multi line note, `end` closes it and gives its
+    /// lines to the state it is attached to
+    pub fn note_line(&mut self, text: &str, end: bool) -> Result<()> {
+        if end {
+            let (target, start, lines) = self.note.take().unwrap();
+            if let Some(id) = target {
+                for (i, l) in lines.iter().enumerate() {
+                    try!(self.behavior(&id, start + i + 1, l));
+                }
+            }
+        } else if let Some(&mut (_, _, ref mut lines)) = self.note.as_mut() {
+            lines.push(text.to_string());
+        }
+        Ok(())
+    }
+
+    /// `}` closing the innermost composite state
+    pub fn close_scope(&mut self, line: usize, text: &str) -> Result<()> {
+        match self.scopes.pop() {
+            Some(_) => Ok(()),
+            None    => Err(self.source.error(line, text, "} without an open state"))
+        }
+    }
+
+    /// A pseudostate declared in the current composite state
+    pub fn pseudostate(&mut self, line: usize, id: &str, kind: VertexKind) {
+        let parent = self.scopes.last().cloned();
+        self.builder.add_vertex(id, kind, parent, self.source.location(line, id));
+    }
+
+    /// A `state` declaration of `id`, `opens` when it is followed by `{`
+    pub fn declare(&mut self, line: usize, id: &str, opens: bool) -> Result<()> {
+        try!(self.state(line, id));
+        if !self.declared.contains(id) {
+            self.declared.insert(id.to_string());
+            let parent   = self.scopes.last().cloned();
+            let location = self.source.location(line, id);
+            if let Some(state) = self.builder.state_mut(id) {
+                state.parent   = parent;
+                state.location = location;
+            }
+        }
+        if opens {
+            self.scopes.push(id.to_string());
+        }
+        Ok(())
+    }
+
+    /// A description or note line of state `id`, only `entry / code` and `exit / code` mean something
+    pub fn behavior(&mut self, id: &str, line: usize, text: &str) -> Result<()> {
+        let text = text.trim();
+        for kind in ["entry", "exit"].iter() {
+            if !text.starts_with(kind) {
+                continue
+            }
+            let rest = text[kind.len()..].trim_start();
+            if !(rest.starts_with('/') || rest.starts_with(':')) {
+                continue
+            }
+            let code = rest[1..].trim().to_string();
+            let location = self.source.location(line, text);
+            return match self.builder.state_mut(id) {
+                Some(state) => {
+                    let old = if *kind == "entry" { state.entry.take() } else { state.exit.take() };
+                    let new = join_code(old.into_iter().chain(Some(code)).collect());
+                    if *kind == "entry" { state.entry = new } else { state.exit = new }
+                    Ok(())
+                },
+                None        => Err(HsmGenError::model(format!("{} code for {} which is not a state", kind, id)).with_location(location))
+            }
+        }
+        Ok(())
+    }
+
+    /// `source --> target : label`, `[*]` is the initial or final state of
+    /// the current composite state. Gives the id of the source.
+    pub fn transition(&mut self, line: usize, source: &str, target: &str, label: &str) -> Result<String> {
+        let parent = self.scopes.last().cloned();
+        let scope = parent.clone().unwrap_or("top".to_string());
+        let source = match source {
+            "[*]" => {
+                let id = format!("{}_initial", scope);
+                self.builder.add_vertex(&id, VertexKind::Initial, parent.clone(), self.source.location(line, source));
+                id
+            },
+            x     => { try!(self.vertex(line, x)); x.to_string() }
+        };
+        let target = match target {
+            "[*]" => {
+                let id = format!("{}_final", scope);
+                self.builder.add_vertex(&id, VertexKind::Final, parent.clone(), self.source.location(line, target));
+                id
+            },
+            x     => { try!(self.vertex(line, x)); x.to_string() }
+        };
+        let label    = Label::parse(label);
+        let location = self.source.location(line, &source);
+        let mut t = Transition::new(&format!("line{}", line + 1), &source, &target);
+        t.guard    = label.guard;
+        t.effect   = label.effect;
+        t.location = location.clone();
+        if let Some(ref trigger) = label.trigger {
+            t.trigger = Some(try!(Event::from_trigger(trigger, &source, location)));
+        }
+        self.builder.add_transition(t);
+        Ok(source)
+    }
+
+    /// A state or pseudostate used in a transition, created if it is not known yet
+    fn vertex(&mut self, line: usize, id: &str) -> Result<()> {
+        if self.builder.vertex_kind(id).is_some() {
+            return Ok(())
+        }
+        if id.starts_with('[') {
+            return Err(self.source.error(line, id, format!("{} pseudostates are not supported", id)))
+        }
+        self.state(line, id)
+    }
+
+    /// State `id` in the current composite state, created if it is not known yet
+    pub fn state(&mut self, line: usize, id: &str) -> Result<()> {
+        if !is_ident(id) {
+            return Err(self.source.error(line, id, format!("invalid state name {:?}", id)))
+        }
+        if !self.builder.has_state(id) && self.builder.vertex_kind(id).is_none() {
+            let mut state = State::new(id, id);
+            state.parent   = self.scopes.last().cloned();
+            state.location = self.source.location(line, id);
+            self.builder.add_state(state);
+        }
+        Ok(())
+    }
+
+    /// The states of the diagram, `end` is the line after it
+    pub fn build(mut self, end: usize) -> Result<HashMap<String, State>> {
+        if let Some(scope) = self.scopes.pop() {
+            try!(self.diagnostics.recover(self.source.error(end.saturating_sub(1), "", format!("state {} is not closed with }}", scope))));
+        }
+        self.builder.build(self.diagnostics)
+    }
+}
+
+pub fn is_ident(text: &str) -> bool {
+    !text.is_empty()
+        && !text.starts_with(|c: char| c.is_digit(10))
+        && text.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
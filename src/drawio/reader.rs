@@ -0,0 +1,377 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use std::io::prelude::*;
+use std::collections::HashMap;
+
+use sxd_document::Package;
+use sxd_document::dom::Element;
+use rustc_serialize::base64::FromBase64;
+use flate2::read::DeflateDecoder;
+
+use ::error::{HsmGenError, Result, Diagnostics};
+use ::location::{SourceLocation, index_xml_ids, read_source};
+use ::xml::{parse_document, child_elements, descendants_named, first_child, text};
+use ::ir::{State, Transition, Event, Label, ModelBuilder, VertexKind, join_code, ModelReader};
+
+
+/// Reads state diagrams drawn in draw.io / diagrams.net.
+///
+/// The first page of the file is read, whether it is saved as plain XML or
+/// compressed. Shapes from the UML state palette are states (rounded
+/// rectangles, swimlanes and `umlState` shapes), initial and final states and
+/// choices, a state drawn inside another one is its substate and edge labels
+/// are `event [guard] / effect`. Other shapes are ignored with a warning. Lines of a state's
+/// text starting with `entry /` or `exit /` give its entry and exit code.
+pub struct DrawioReader {
+    file        : String,
+    package     : Package,
+    pages       : usize,
+    diagnostics : Diagnostics,
+    /// Empty for compressed diagrams, their ids have no place in the file
+    locations   : HashMap<String, SourceLocation>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Shape {
+    Layer,
+    State,
+    Initial,
+    Final,
+    Choice,
+    Bar,
+    Text,
+    EdgeLabel,
+    Edge,
+    /// Notes and groups, which are ignored
+    Other,
+    /// Shapes that are not from the UML state palette
+    Unknown,
+}
+
+#[derive(Debug)]
+struct Cell {
+    id     : String,
+    value  : String,
+    shape  : Shape,
+    parent : Option<String>,
+    source : Option<String>,
+    target : Option<String>,
+}
+
+impl DrawioReader {
+    pub fn from_file(file: &str) -> Result<Self> {
+        let s = try!(read_source(file));
+        let outer = try!(parse_document(file, &s));
+        // A compressed page keeps its graph model as text in the diagram element
+        let (compressed, pages) = {
+            let doc = outer.as_document();
+            let mut diagrams = Vec::new();
+            for el in doc.root().children().into_iter().filter_map(|x| x.element()) {
+                descendants_named(el, "diagram", &mut diagrams);
+            }
+            let compressed = match diagrams.first() {
+                Some(d) if first_child(*d, "mxGraphModel").is_none() => Some(text(*d)),
+                _                                                     => None
+            };
+            (compressed, diagrams.len())
+        };
+        let (package, locations) = match compressed {
+            Some(data) => {
+                let xml = try!(inflate(file, &data));
+                (try!(parse_document(file, &xml)), HashMap::new())
+            },
+            None       => (outer, index_xml_ids(file, &s))
+        };
+        Ok(DrawioReader {
+            file        : file.to_string(),
+            package     : package,
+            pages       : pages,
+            diagnostics : Diagnostics::new(),
+            locations   : locations,
+        })
+    }
+
+    fn read_edge(&self, builder: &mut ModelBuilder, by_id: &HashMap<String, &Cell>, cells: &[Cell], edge: &Cell) -> Result<()> {
+        // The label is the value of the edge or of label shapes placed on it
+        let mut text = vec![edge.value.clone()];
+        text.extend(cells.iter().filter(|x| x.shape == Shape::EdgeLabel && x.parent.as_ref() == Some(&edge.id)).map(|x| x.value.clone()));
+        let label = Label::parse(&text.join(" ").replace('\n', " "));
+        let source = match edge.source.as_ref().and_then(|x| self.endpoint(by_id, x)) {
+            Some(x) => x,
+            None    => return Err(self.error(edge, format!("edge {:?} is not connected to a state at its start", label_text(&text))))
+        };
+        let target = match edge.target.as_ref().and_then(|x| self.endpoint(by_id, x)) {
+            Some(x) => x,
+            None    => return Err(self.error(edge, format!("edge {:?} is not connected to a state at its end", label_text(&text))))
+        };
+        let location = self.locations.get(&edge.id).cloned();
+        let mut t = Transition::new(&edge.id, &source.id, &target.id);
+        t.guard    = label.guard;
+        t.effect   = label.effect;
+        t.location = location.clone();
+        if let Some(ref trigger) = label.trigger {
+            let state = source.value.lines().next().unwrap_or("").trim();
+            t.trigger = Some(try!(Event::from_trigger(trigger, state, location)));
+        }
+        builder.add_transition(t);
+        Ok(())
+    }
+
+    /// The state or pseudostate an edge end at cell `id` is connected to,
+    /// text shapes inside a state stand for the state
+    fn endpoint<'c>(&self, by_id: &HashMap<String, &'c Cell>, id: &str) -> Option<&'c Cell> {
+        let mut cur = by_id.get(id).cloned();
+        while let Some(c) = cur {
+            match c.shape {
+                Shape::State | Shape::Initial | Shape::Final | Shape::Choice => return Some(c),
+                Shape::Text                                                   => {},
+                _                                                             => return None
+            }
+            cur = c.parent.as_ref().and_then(|x| by_id.get(x)).cloned();
+        }
+        None
+    }
+
+    /// The id of the state a shape is drawn in
+    fn owner(&self, by_id: &HashMap<String, &Cell>, cell: &Cell) -> Option<String> {
+        let mut cur = cell.parent.as_ref().and_then(|x| by_id.get(x)).cloned();
+        while let Some(c) = cur {
+            if c.shape == Shape::State {
+                return Some(c.id.clone())
+            }
+            cur = c.parent.as_ref().and_then(|x| by_id.get(x)).cloned();
+        }
+        None
+    }
+
+    fn error<S: Into<String>>(&self, cell: &Cell, msg: S) -> HsmGenError {
+        HsmGenError::model(msg).context("mxCell", cell.id.clone()).with_location(self.locations.get(&cell.id).cloned())
+    }
+}
+
+impl ModelReader for DrawioReader {
+    fn read_states(&self) -> Result<HashMap<String, State>> {
+        if self.pages > 1 {
+            self.diagnostics.warn(HsmGenError::model(format!("{} has {} pages, only the first one is read", self.file, self.pages)));
+        }
+        let doc = self.package.as_document();
+        let mut models = Vec::new();
+        for el in doc.root().children().into_iter().filter_map(|x| x.element()) {
+            if el.name().local_part() == "mxGraphModel" {
+                models.push(el);
+            }
+            descendants_named(el, "mxGraphModel", &mut models);
+        }
+        let root = match models.first().and_then(|x| first_child(*x, "root")) {
+            Some(x) => x,
+            None    => return Err(HsmGenError::parse(format!("no diagram in {}", self.file)))
+        };
+        let cells = child_elements(root).into_iter().filter_map(cell).collect::<Vec<Cell>>();
+        let by_id = cells.iter().map(|x| (x.id.clone(), x)).collect::<HashMap<String, &Cell>>();
+
+        let mut builder = ModelBuilder::new();
+        for c in cells.iter() {
+            let parent = self.owner(&by_id, c);
+            let res = match c.shape {
+                Shape::State   => {
+                    let mut lines = c.value.lines().map(|x| x.trim()).filter(|x| !x.is_empty());
+                    match lines.next() {
+                        Some(name) => {
+                            let mut state = State::new(&c.id, name);
+                            state.parent   = parent;
+                            state.location = self.locations.get(&c.id).cloned();
+                            builder.add_state(state);
+                            Ok(())
+                        },
+                        None       => Err(self.error(c, "state without name")),
+                    }
+                },
+                Shape::Initial => { builder.add_vertex(&c.id, VertexKind::Initial, parent, self.locations.get(&c.id).cloned()); Ok(()) },
+                Shape::Final   => { builder.add_vertex(&c.id, VertexKind::Final, parent, self.locations.get(&c.id).cloned()); Ok(()) },
+                Shape::Choice  => { builder.add_vertex(&c.id, VertexKind::Choice, parent, self.locations.get(&c.id).cloned()); Ok(()) },
+                Shape::Bar     => Err(self.error(c, "fork and join bars are not supported")),
+                Shape::Unknown => {
+                    self.diagnostics.warn(self.error(c, format!("shape {:?} is not from the UML state palette and was ignored", label_text(&[c.value.clone()]))));
+                    Ok(())
+                },
+                _              => Ok(())
+            };
+            if let Err(e) = res {
+                try!(self.diagnostics.recover(e));
+            }
+        }
+        // Text of a state, its first line is the name, and text shapes inside it
+        for c in cells.iter() {
+            let (owner, lines) = match c.shape {
+                Shape::State => (Some(c.id.clone()), c.value.lines().skip(1).collect::<Vec<&str>>()),
+                Shape::Text  => (self.owner(&by_id, c), c.value.lines().collect::<Vec<&str>>()),
+                _            => continue
+            };
+            let owner = match owner {
+                Some(x) => x,
+                None    => continue
+            };
+            if let Some(state) = builder.state_mut(&owner) {
+                for line in lines {
+                    add_behavior(state, line);
+                }
+            }
+        }
+        for c in cells.iter().filter(|x| x.shape == Shape::Edge) {
+            if let Err(e) = self.read_edge(&mut builder, &by_id, &cells, c) {
+                try!(self.diagnostics.recover(e));
+            }
+        }
+        builder.build(&self.diagnostics)
+    }
+
+    fn diagnostic_log(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+}
+
+/// Reads an `mxCell`, or the `object` wrapping one when the shape has custom properties
+fn cell(el: Element) -> Option<Cell> {
+    let (outer, inner) = match el.name().local_part() {
+        "mxCell"                => (el, el),
+        "object" | "UserObject" => match first_child(el, "mxCell") {
+            Some(x) => (el, x),
+            None    => return None
+        },
+        _                       => return None
+    };
+    let attr = |e: Element, name: &str| e.attribute_value(name).map(|x| x.to_string());
+    let style = attr(inner, "style").unwrap_or(String::new());
+    let styles = style.split(';').map(|x| x.trim()).collect::<Vec<&str>>();
+    let has = |s: &str| styles.contains(&s);
+    let shape = if attr(inner, "edge").as_ref().map(|x| x.as_str()) == Some("1") {
+        Shape::Edge
+    } else if attr(inner, "vertex").as_ref().map(|x| x.as_str()) != Some("1") {
+        Shape::Layer
+    } else if has("edgeLabel") {
+        Shape::EdgeLabel
+    } else if has("shape=startState") {
+        Shape::Initial
+    } else if has("shape=endState") {
+        Shape::Final
+    } else if has("rhombus") || has("shape=rhombus") {
+        Shape::Choice
+    } else if has("shape=line") || has("shape=fork") {
+        Shape::Bar
+    } else if has("text") {
+        Shape::Text
+    } else if has("shape=note") || has("note") || has("group") {
+        Shape::Other
+    } else if has("rounded=1") || has("swimlane") || has("shape=umlState") {
+        Shape::State
+    } else {
+        Shape::Unknown
+    };
+    // Only labels with html=1 are HTML, others are the text itself
+    let value = attr(outer, "value").or(attr(outer, "label")).unwrap_or(String::new());
+    Some(Cell {
+        id     : attr(outer, "id").unwrap_or(String::new()),
+        value  : if has("html=1") { plain_text(&value) } else { value },
+        shape  : shape,
+        parent : attr(inner, "parent"),
+        source : attr(inner, "source"),
+        target : attr(inner, "target"),
+    })
+}
+
+/// Adds an `entry / code` or `exit / code` line to the state
+fn add_behavior(state: &mut State, line: &str) {
+    let line = line.trim();
+    for kind in ["entry", "exit"].iter() {
+        if !line.starts_with(kind) {
+            continue
+        }
+        let rest = line[kind.len()..].trim_start();
+        if !rest.starts_with('/') {
+            continue
+        }
+        let code = rest[1..].trim().to_string();
+        if *kind == "entry" {
+            state.entry = join_code(state.entry.take().into_iter().chain(Some(code)).collect());
+        } else {
+            state.exit = join_code(state.exit.take().into_iter().chain(Some(code)).collect());
+        }
+    }
+}
+
+fn label_text(text: &[String]) -> String {
+    text.iter().map(|x| x.trim()).filter(|x| !x.is_empty()).collect::<Vec<&str>>().join(" ")
+}
+
+/// The text of a value with html=1, tags dropped and line breaks kept
+fn plain_text(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        let end = rest[start..].find('>').map(|x| start + x + 1).unwrap_or(rest.len());
+        let tag = rest[start + 1..end].trim_start_matches('/').to_lowercase();
+        if tag.starts_with("br") || tag.starts_with("div") || tag.starts_with("p>") || tag.starts_with("hr") {
+            out.push('\n');
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    let entities = [ ("&nbsp;", " "), ("&lt;", "<"), ("&gt;", ">"), ("&quot;", "\""), ("&#39;", "'"), ("&amp;", "&") ];
+    for &(entity, ch) in entities.iter() {
+        out = out.replace(entity, ch);
+    }
+    out
+}
+
+/// Decodes a compressed page: base64 of deflated, url encoded XML
+fn inflate(file: &str, data: &str) -> Result<String> {
+    let bytes = try!(data.from_base64().map_err(|e|
+        HsmGenError::parse(format!("could not decode the diagram in {}: {}", file, e))
+    ));
+    let mut inflated = String::new();
+    try!(DeflateDecoder::new(&bytes[..]).read_to_string(&mut inflated).map_err(|e|
+        HsmGenError::parse(format!("could not inflate the diagram in {}: {}", file, e))
+    ));
+    Ok(url_decode(&inflated))
+}
+
+fn url_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = ::std::str::from_utf8(&bytes[i + 1..i + 3]).ok().and_then(|x| u8::from_str_radix(x, 16).ok());
+            if let Some(b) = hex {
+                out.push(b);
+                i += 3;
+                continue
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
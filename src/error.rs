@@ -0,0 +1,210 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::error::Error;
+use std::result;
+
+use ::location::SourceLocation;
+
+
+/// Broad category of a failure while reading a model or generating code
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ErrorKind {
+    Io,
+    Parse,
+    XPath,
+    Model,
+    Generate,
+}
+
+/// Error returned by `XmiReader` and `HsmGenerator`.
+///
+/// `element` is the kind of the offending model element (for example `uml:State`),
+/// `id` its xmi:id and `location` where it starts in the source file, when the
+/// failure can be attributed to one.
+#[derive(Debug, Clone)]
+pub struct HsmGenError {
+    pub kind     : ErrorKind,
+    pub element  : Option<String>,
+    pub id       : Option<String>,
+    pub location : Option<SourceLocation>,
+    pub message  : String,
+}
+
+pub type Result<T> = result::Result<T, HsmGenError>;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found while reading a model that did not stop the reader
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity : Severity,
+    pub error    : HsmGenError,
+}
+
+/// Diagnostics collected by a model reader.
+///
+/// In collect-all mode recoverable model problems are recorded and reading
+/// continues, otherwise the first one is returned as an error. They may be
+/// recorded from several threads.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    collect_all : AtomicBool,
+    list        : Mutex<Vec<Diagnostic>>,
+}
+
+impl HsmGenError {
+    pub fn new<S: Into<String>>(kind: ErrorKind, message: S) -> Self {
+        HsmGenError {
+            kind     : kind,
+            element  : None,
+            id       : None,
+            location : None,
+            message  : message.into(),
+        }
+    }
+
+    pub fn io<S: Into<String>>(message: S) -> Self {
+        Self::new(ErrorKind::Io, message)
+    }
+
+    pub fn parse<S: Into<String>>(message: S) -> Self {
+        Self::new(ErrorKind::Parse, message)
+    }
+
+    pub fn xpath<S: Into<String>>(message: S) -> Self {
+        Self::new(ErrorKind::XPath, message)
+    }
+
+    pub fn model<S: Into<String>>(message: S) -> Self {
+        Self::new(ErrorKind::Model, message)
+    }
+
+    pub fn generate<S: Into<String>>(message: S) -> Self {
+        Self::new(ErrorKind::Generate, message)
+    }
+
+    /// Attaches the offending element, unless the error already names one
+    pub fn context<E: Into<String>, I: Into<String>>(mut self, element: E, id: I) -> Self {
+        if self.element.is_none() && self.id.is_none() {
+            self.element = Some(element.into());
+            self.id      = Some(id.into());
+        }
+        self
+    }
+
+    /// Attaches a source location, unless the error already has one
+    pub fn with_location(mut self, location: Option<SourceLocation>) -> Self {
+        if self.location.is_none() {
+            self.location = location;
+        }
+        self
+    }
+}
+
+impl fmt::Display for HsmGenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref loc) = self.location {
+            try!(write!(f, "{}: ", loc));
+        }
+        try!(write!(f, "{}", self.description()));
+        match (&self.element, &self.id) {
+            (&Some(ref el), &Some(ref id)) => try!(write!(f, " in {} (id {})", el, id)),
+            (&Some(ref el), &None        ) => try!(write!(f, " in {}", el)),
+            (&None,         &Some(ref id)) => try!(write!(f, " in element with id {}", id)),
+            (&None,         &None        ) => {},
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl Error for HsmGenError {
+    fn description(&self) -> &str {
+        match self.kind {
+            ErrorKind::Io       => "I/O error",
+            ErrorKind::Parse    => "XML parse error",
+            ErrorKind::XPath    => "XPath error",
+            ErrorKind::Model    => "Model error",
+            ErrorKind::Generate => "Code generation error",
+        }
+    }
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Diagnostics::default()
+    }
+
+    pub fn set_collect_all(&self, collect_all: bool) {
+        self.collect_all.store(collect_all, Ordering::SeqCst);
+    }
+
+    pub fn collect_all(&self) -> bool {
+        self.collect_all.load(Ordering::SeqCst)
+    }
+
+    /// Records `err` and returns Ok in collect-all mode, returns it otherwise
+    pub fn recover(&self, err: HsmGenError) -> Result<()> {
+        if self.collect_all() {
+            self.push(Diagnostic { severity: Severity::Error, error: err });
+            Ok(())
+        } else {
+            Err(err)
+        }
+    }
+
+    pub fn warn(&self, err: HsmGenError) {
+        self.push(Diagnostic { severity: Severity::Warning, error: err });
+    }
+
+    fn push(&self, diagnostic: Diagnostic) {
+        // A thread that panicked while holding the lock left the list as it was
+        let mut list = self.list.lock().unwrap_or_else(|e| e.into_inner());
+        list.push(diagnostic);
+    }
+
+    /// Replaces the diagnostics recorded so far with `list`
+    pub fn replace(&self, list: Vec<Diagnostic>) {
+        *self.list.lock().unwrap_or_else(|e| e.into_inner()) = list;
+    }
+
+    pub fn to_vec(&self) -> Vec<Diagnostic> {
+        self.list.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: {}", self.error),
+            Severity::Error   => write!(f, "error: {}", self.error),
+        }
+    }
+}
@@ -25,290 +25,370 @@ use std::io::prelude::*;
 use std::fs::File;
 use std::collections::{HashSet, HashMap};
 
-use syntax::ast::{Crate, Variant, Item, Visibility, TokenTree, Expr, Arm, Ident, EnumDef};
-use syntax::ptr::P;
-use syntax::ext::base::ExtCtxt;
-use syntax::ext::build::AstBuilder;
-use syntax::print::pprust::{NoAnn, print_crate};
-use syntax::parse::token::{str_to_ident, gensym, Token, IdentStyle};
-use syntax::codemap::{ExpnId, ExpnInfo, ExpnFormat, NameAndSpan, DUMMY_SP};
-use syntax::feature_gate::GatedCfgAttr;
-use rustc_driver::driver::phase_1_parse_input;
+use ::error::{HsmGenError, Result};
+use ::ir::{State, Event, SignalAttribute, CondAction, Action, MachineHints};
 
-use ::ir::{State, Event, CondAction, Action};
-use super::inner::Inner;
 
+const CRATE_SRC : &'static str = "//Generated by hsm-gen, modifications will be lost when regenerating
+use hsm;
+use time::SteadyTime;
+use enum_timer::{TimerEvent, TimerStorage};
+use super::hsm_uses::*;
+";
+const FN_CRATE_SRC: &'static str = "//Generated by hsm-gen, modifications will be lost when regenerating
+use hsm;
+use super::hsm_uses::SharedData;
+";
 
-pub struct HsmGenerator {
-    inner               : Inner,
-    krate               : Crate,
-    feature_gated_cfgs  : Vec<GatedCfgAttr>,
 
+/// Builds the source of a state machine for rust-hsm item by item, `print`
+/// writes the items created so far.
+///
+/// Guards, effects and entry and exit code are Rust code the model gives as
+/// text, they are put in the generated source as they are.
+pub struct HsmGenerator {
+    prefix  : bool,
+    items   : Vec<String>,
+    hints   : MachineHints,
 }
 impl HsmGenerator {
+    /// `prefix` starts the output with the uses the state machine needs,
+    /// otherwise with those of the function stubs
     pub fn new(prefix: bool) -> Self {
-        let inner = Inner::new(prefix);
-        let krate = phase_1_parse_input(&inner.sess, inner.cfg.clone(), &inner.input);
         HsmGenerator {
-            inner              : inner,
-            krate              : krate,
-            feature_gated_cfgs : Vec::new(),
+            prefix : prefix,
+            items  : Vec::new(),
+            hints  : MachineHints::default(),
         }
     }
 
-    fn extctxt(&mut self) -> ExtCtxt {
-        let mut cx = ExtCtxt::new(&self.inner.sess.parse_sess,
-                                  self.inner.cfg.clone(),
-                                  self.inner.ecfg(),
-                                  &mut self.feature_gated_cfgs);
-        cx.backtrace = ExpnId::from_u32(0);
-        cx.codemap().record_expansion(
-            ExpnInfo {
-                call_site: self.krate.span,
-                callee: NameAndSpan {
-                    format: ExpnFormat::MacroBang(gensym("hsm_generate")),
-                    allow_internal_unstable: true,
-                    span: None,
-                },
-            }
-        );
-        cx
+    /// Sets the hints of the RustHsm profile for the items created after
+    pub fn set_hints(&mut self, hints: MachineHints) {
+        self.hints = hints;
+    }
+
+    fn visibility(&self) -> &'static str {
+        if self.hints.public { "pub " } else { "" }
+    }
+
+    /// The generated source
+    fn source(&self) -> String {
+        let mut src = (if self.prefix { CRATE_SRC } else { FN_CRATE_SRC }).to_string();
+        for item in self.items.iter() {
+            src.push('\n');
+            src.push_str(item);
+            src.push('\n');
+        }
+        src
     }
 
-    pub fn print(&self, file: &str) {
-        let src_nam = self.inner.src_name.clone();
-        let src = self.inner.sess.codemap().get_filemap(&src_nam[..])
-                            .src
-                            .as_ref()
-                            .unwrap()
-                            .as_bytes()
-                            .to_vec();
-        let mut rdr = &src[..];
-        let mut out = Vec::new();
-        let ann = NoAnn;
-        {
-            let out_w: &mut Write = &mut out;
-            print_crate( self.inner.sess.codemap(),
-                         self.inner.sess.diagnostic(),
-                         &self.krate,
-                         src_nam,
-                         &mut rdr,
-                         box out_w,
-                         &ann,
-                         false).expect("Could not format crate");
+    pub fn print(&self, file: &str) -> Result<()> {
+        let mut f = try!(File::create(file).map_err(|e|
+            HsmGenError::io(format!("could not create {}: {}", file, e))
+        ));
+        f.write_all(self.source().as_bytes()).map_err(|e|
+            HsmGenError::io(format!("could not write {}: {}", file, e))
+        )
+    }
+
+    /// `code` with every line indented by one level
+    fn indent(code: &str) -> String {
+        code.lines()
+            .map(|line| if line.is_empty() { String::new() } else { format!("    {}", line) })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// A block of the statements `stmts` that ends with the expression `expr`
+    fn block(stmts: Vec<String>, expr: &str) -> String {
+        let mut lines = stmts.iter().map(|x| format!("{};", x)).collect::<Vec<_>>();
+        lines.push(expr.to_string());
+        format!("{{\n{}\n}}", Self::indent(&lines.join("\n")))
+    }
+
+    /// `match` arm of `pat`, `attrs` are put in front of it
+    fn arm(attrs: &[String], pat: &str, expr: &str) -> String {
+        let mut lines = attrs.to_vec();
+        lines.push(format!("{} => {},", pat, expr));
+        lines.join("\n")
+    }
+
+    /// `///` doc comments for the lines of `doc`
+    fn doc_comments(doc: Option<&str>) -> Vec<String> {
+        doc.map_or(Vec::new(), |doc| doc.lines().map(|line| match line.trim_end() {
+            ""   => "///".to_string(),
+            line => format!("/// {}", line),
+        }).collect())
+    }
+
+    /// `//` comments for the docs of the transitions of an arm, doc comments
+    /// are not allowed on match arms
+    fn arm_comments(ca_vec: &[CondAction]) -> Vec<String> {
+        let mut comments = Vec::new();
+        for ca in ca_vec.iter() {
+            if let Some(ref doc) = ca.doc {
+                comments.extend(doc.lines().map(|line| match line.trim_end() {
+                    ""   => "//".to_string(),
+                    line => format!("// {}", line),
+                }));
+            }
+            if let Action::Diverge {ref cond_act_vec, ..} = ca.action {
+                comments.extend(Self::arm_comments(cond_act_vec));
+            }
         }
-        // print!("{}", String::from_utf8(out).unwrap());
-        let mut f = File::create(file).expect("Could not create file");
-        f.write(&out).expect("Could not write to file");
+        comments
     }
 
-    fn create_enum(&mut self, name: &str, vm: HashMap<String, Option<String>>) -> P<Item> {
-        let cx = self.extctxt();
-        let mut variants: Vec<P<Variant>> = Vec::new();
-        let mut vm_vec = vm.iter().collect::<Vec<(&String, &Option<String>)>>();
+    /// `name` preceded by `#[doc]` attributes for the lines of `doc`, printed as is
+    fn documented(name: &str, doc: Option<&String>) -> String {
+        let attrs = doc.map_or(Vec::new(), |doc| doc.lines().map(|line|
+            format!("#[doc = {:?}]", format!(" {}", line.trim_end()).trim_end())
+        ).collect());
+        attrs.into_iter().chain(Some(name.to_string())).collect::<Vec<_>>().join(" ")
+    }
+
+    /// Builds enum `name` with a variant for every key of `vm`, documented by
+    /// `docs`. A variant with one field is a tuple variant, one with several
+    /// fields a struct variant.
+    fn create_enum(&self, name: &str, vm: HashMap<String, Vec<(Option<String>, String)>>, docs: HashMap<String, String>) -> String {
+        let mut derive = vec!("Debug".to_string(), "Clone".to_string());
+        let extra = self.hints.derive.iter().filter(|x| !derive.contains(x)).cloned().collect::<Vec<_>>();
+        derive.extend(extra);
+        let mut variants = Vec::new();
+        let mut vm_vec = vm.iter().collect::<Vec<(&String, &Vec<(Option<String>, String)>)>>();
         vm_vec.sort_by(|a,b| a.0.cmp(b.0));
-        for (var_name, opt_intern) in vm_vec {
-            let variant = cx.variant(
-                DUMMY_SP,
-                str_to_ident(var_name),
-                { if let Some(int_name) = opt_intern.as_ref() {
-                    vec!(cx.ty_ident(DUMMY_SP, str_to_ident(int_name)))
-                } else {
-                    Vec::new()
-                }}
-            );
-            variants.push(P(variant));
+        for (var_name, fields) in vm_vec {
+            variants.extend(Self::doc_comments(docs.get(var_name).map(|x| x.as_str())));
+            variants.push(match fields.len() {
+                0 => format!("{},", var_name),
+                1 => format!("{}({}),", var_name, fields[0].1),
+                _ => format!("{} {{ {} }},", var_name, fields.iter()
+                        .map(|&(ref field, ref ty)| format!("{}: {}", field.as_ref().map(|x| x.as_str()).unwrap_or("_"), ty))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                     ),
+            });
         }
-        cx.item_enum(
-            DUMMY_SP,
-            str_to_ident(name),
-            EnumDef{ variants: variants }
-        ).map(|mut x| {
-            x.vis = Visibility::Public;
-            x.attrs.push(quote_attr!(&cx, #[derive(Debug, Clone)]));
-            x
-        })
+        let body = if variants.is_empty() { String::new() } else { format!("\n{}\n", Self::indent(&variants.join("\n"))) };
+        format!("#[derive({})]\n{}enum {} {{{}}}", derive.join(", "), self.visibility(), name, body)
+    }
+
+    /// Adds the variant of the signal `name` with `fields`. Several events
+    /// may stand for one signal, of which only some know its attributes and doc.
+    fn add_signal(signals: &mut HashMap<String, Vec<(Option<String>, String)>>, docs: &mut HashMap<String, String>,
+                  name: &str, fields: Vec<(Option<String>, String)>, doc: Option<&String>) -> Result<()> {
+        if name == "Timeout" || name == "Poll" {
+            return Err(HsmGenError::generate(format!("signal {} has the name of a generated event, rename it", name)))
+        }
+        match signals.get(name) {
+            Some(known) if !known.is_empty() && !fields.is_empty() && *known != fields => return Err(HsmGenError::generate(
+                format!("signal {} is used with different attributes", name)
+            )),
+            Some(known) if fields.is_empty() || !known.is_empty() => {},
+            _                                                    => { signals.insert(name.to_string(), fields); },
+        }
+        if let Some(doc) = doc {
+            docs.entry(name.to_string()).or_insert(doc.clone());
+        }
+        Ok(())
     }
 
-    pub fn create_event_enum(&mut self, hm: &HashMap<String, State>) {
-        let mut time_evts: HashMap<String, Option<String>> = HashMap::new();
-        let mut signals  : HashMap<String, Option<String>> = HashMap::new();
-        signals.insert("Timeout".to_string(), Some("Timeout".to_string()));
-        hm.values().map(|x| x.actions.keys().map(|e| match *e {
-            Event::Signal {ref name, ..} => {
-                let nam_parts = name.split("(").collect::<Vec<&str>>();
-                let int_val = {
-                    if nam_parts.len() == 1 {
-                        None
-                    } else {
-                        let int_parts = nam_parts[1].split("::").collect::<Vec<&str>>();
-                        if int_parts.len() == 1 {
-                            Some(nam_parts[0].to_string())
+    pub fn create_event_enum(&mut self, hm: &HashMap<String, State>) -> Result<()> {
+        let mut time_evts: HashMap<String, Vec<(Option<String>, String)>> = HashMap::new();
+        let mut signals  : HashMap<String, Vec<(Option<String>, String)>> = HashMap::new();
+        let mut docs     : HashMap<String, String> = HashMap::new();
+        let mut poll = false;
+        signals.insert("Timeout".to_string(), vec!((None, "Timeout".to_string())));
+        // Sorted so that the variants do not depend on the order of the states
+        let mut events = hm.values().flat_map(|x| x.actions.keys()).collect::<Vec<&Event>>();
+        events.sort();
+        for e in events {
+            match *e {
+                Event::Signal {ref name, ref attributes, ref doc, ..} if !attributes.is_empty() => {
+                    let fields = attributes.iter().map(|a| (Some(a.name.clone()), a.ty.clone())).collect();
+                    try!(Self::add_signal(&mut signals, &mut docs, name, fields, doc.as_ref()));
+                },
+                Event::Signal {ref name, ref doc, ..} => {
+                    let nam_parts = name.split("(").collect::<Vec<&str>>();
+                    let int_val = {
+                        if nam_parts.len() == 1 {
+                            None
                         } else {
-                            Some(int_parts[0].to_string())
+                            let int_parts = nam_parts[1].split("::").collect::<Vec<&str>>();
+                            if int_parts.len() == 1 {
+                                Some(nam_parts[0].to_string())
+                            } else {
+                                Some(int_parts[0].to_string())
+                            }
                         }
-                    }
-                };
-                signals.insert(nam_parts[0].to_string(), int_val);
-            },
-            Event::Time   {ref name, ..} => { time_evts.insert(name.to_string(), None); },
-            Event::Any | Event::UserAny  => {},
-        }).count()).count();
-        // debug!("{:#?}", time_evts);
-        // debug!("{:#?}", signals);
-        let time_enum = self.create_enum("Timeout", time_evts);
-        let wrapped_time_enum = {
-            let cx = self.extctxt();
-            quote_item!(&cx, et_create_enum_timer!{TimeoutStorage; $time_enum})
-        }.unwrap();
-        self.krate.module.items.push(wrapped_time_enum);
-        let event_enum = self.create_enum("Events", signals);
-        self.krate.module.items.push(event_enum);
+                    };
+                    let fields = int_val.into_iter().map(|x| (None, x)).collect();
+                    try!(Self::add_signal(&mut signals, &mut docs, nam_parts[0], fields, doc.as_ref()));
+                },
+                Event::Time   {ref name, ..} => { time_evts.insert(name.to_string(), Vec::new()); },
+                // Change events have no variant of their own, `Poll` asks states to check them
+                Event::Change {..}           => { poll = true; },
+                Event::Any | Event::UserAny  => {},
+            }
+        }
+        if poll {
+            signals.insert("Poll".to_string(), Vec::new());
+            docs.insert("Poll".to_string(), "Checks the conditions of change events".to_string());
+        }
+        let time_enum = self.create_enum("Timeout", time_evts, HashMap::new());
+        self.items.push(format!("et_create_enum_timer!{{\n{}\n}}", Self::indent(&format!("TimeoutStorage;\n{}", time_enum))));
+        let event_enum = self.create_enum("Events", signals, docs);
+        self.items.push(event_enum);
+        Ok(())
     }
 
-    pub fn create_hsm_objects(&mut self, hm: &HashMap<String, State>) {
-        let x = {
-            let cx = self.extctxt();
-            let events = str_to_ident("Events");
-            let st_str = str_to_ident("StateStruct");
-            let st     = str_to_ident("States");
-            let shr_dat= str_to_ident("SharedData");
-            let mut keys_vec = hm.keys().collect::<Vec<&String>>();
-            keys_vec.sort();
-            let states: Vec<TokenTree> = keys_vec
-                .iter()
-                .map(|st_nam| vec![Token::Ident(str_to_ident(st_nam), IdentStyle::Plain)])
-                .collect::<Vec<_>>()
-                .join(&Token::Comma)
-                .into_iter()
-                .map(|t| TokenTree::Token(DUMMY_SP, t))
-                .collect();
-
-            quote_item!(&cx,
-                hsm_define_objects!($st_str, $st, $events, $shr_dat, (
-                    $states
-                ));
-            ).unwrap()
-        };
-        self.krate.module.items.push(x);
+    pub fn create_hsm_objects(&mut self, hm: &HashMap<String, State>) -> Result<()> {
+        let mut keys_vec = hm.keys().collect::<Vec<&String>>();
+        keys_vec.sort();
+        // The comments of a state document its struct, the macro passes
+        // the attributes in front of a state name on to it
+        let states = keys_vec
+            .iter()
+            .map(|st_nam| Self::documented(st_nam, hm[*st_nam].doc.as_ref()))
+            .collect::<Vec<_>>()
+            .join(",\n");
+        self.items.push(format!("hsm_define_objects!(StateStruct, States, Events, SharedData, (\n{}\n));", Self::indent(&states)));
+        Ok(())
     }
 
-    pub fn create_state_parent_impls(&mut self, hm: &HashMap<String, State>) {
-        let x = {
-            let cx = self.extctxt();
-            let st = str_to_ident("States");
-            let mut states_vec = hm.iter().collect::<Vec<(&String, &State)>>();
-            states_vec.sort_by(|a,b| a.0.cmp(b.0));
-            let par_lst: Vec<TokenTree> = states_vec
-                .iter()
-                .map(|x| x.1)
-                .map(|state| vec![
-                    Token::Ident(str_to_ident(state.name.as_str()), IdentStyle::Plain),
-                    Token::RArrow,
-                    Token::Ident(str_to_ident(if let Some(ref x) = state.parent {x} else {"None"} ), IdentStyle::Plain)
-                ])
-                .collect::<Vec<_>>()
-                .join(&Token::Comma)
-                .into_iter()
-                .map(|t| TokenTree::Token(DUMMY_SP, t))
-                .collect();
-            quote_item!(&cx,
-                hsm_state_parents!($st; $par_lst);
-            ).unwrap()
-        };
-        self.krate.module.items.push(x);
+    pub fn create_state_parent_impls(&mut self, hm: &HashMap<String, State>) -> Result<()> {
+        let mut states_vec = hm.iter().collect::<Vec<(&String, &State)>>();
+        states_vec.sort_by(|a,b| a.0.cmp(b.0));
+        let par_lst = states_vec
+            .iter()
+            .map(|x| x.1)
+            .map(|state| format!("{} -> {}", state.name, if let Some(ref x) = state.parent {x} else {"None"}))
+            .collect::<Vec<_>>()
+            .join(",\n");
+        self.items.push(format!("hsm_state_parents!(States;\n{}\n);", Self::indent(&par_lst)));
+        Ok(())
     }
 
-    pub fn create_state_impls(&mut self, hm: &HashMap<String, State>) {
-        let events = str_to_ident("Events");
-        let states = str_to_ident("States");
-        let shr_dat= str_to_ident("SharedData");
-        let timeout= str_to_ident("Timeout");
+    pub fn create_state_impls(&mut self, hm: &HashMap<String, State>) -> Result<()> {
+        let cfgs = hm.values()
+            .filter_map(|x| x.cfg.as_ref().map(|pred| (x.name.clone(), pred.clone())))
+            .collect::<HashMap<String, String>>();
         let mut states_vec = hm.iter().collect::<Vec<(&String, &State)>>();
         states_vec.sort_by(|a,b| a.0.cmp(b.0));
         for state in states_vec.iter().map(|x| x.1) {
-            let st_impl = self.create_state_impl(state, &events, &states, &shr_dat, &timeout);
-            self.krate.module.items.push(st_impl);
+            let st_impls = try!(Self::check_cfg(state, hm, &cfgs)
+                                    .and_then(|_| Self::create_state_impl(state, &cfgs))
+                                    .map_err(|e| e.context("uml:State", state.id.clone())
+                                                  .with_location(state.location.clone())));
+            self.items.extend(st_impls);
         }
+        Ok(())
     }
 
-    fn create_enter_exit_arm(cx: &ExtCtxt, opt_func: &Option<String>, evt_type: &str, extra: Vec<P<Expr>>) -> Option<Arm> {
-        if let &Some(ref func_nam) = opt_func {
-            let func_ident = str_to_ident(func_nam);
-            let evt_ident = str_to_ident(evt_type);
-            Some(cx.arm(DUMMY_SP,
-                   vec!(quote_pat!(&cx, hsm::Event::$evt_ident)),
-                   quote_expr!(&cx, {
-                        $func_ident;
-                        $extra;
-                        hsm::Action::Ignore
-                   })
-            ))
-        } else {
-            if extra.len() > 0 {
-                let evt_ident = str_to_ident(evt_type);
-                Some(cx.arm(DUMMY_SP,
-                       vec!(quote_pat!(&cx, hsm::Event::$evt_ident)),
-                       quote_expr!(&cx, {
-                            $extra;
-                            hsm::Action::Ignore
-                       })
-                ))
-            } else {
-                None
+    /// A state gated by `Cfg` is still declared by `hsm_define_objects!` and
+    /// `hsm_state_parents!`, only its impl and the transitions into it can be
+    /// left out. States whose children or initial state would be left
+    /// dangling cannot be gated.
+    fn check_cfg(state: &State, hm: &HashMap<String, State>, cfgs: &HashMap<String, String>) -> Result<()> {
+        if let Some(ref parent) = state.parent {
+            if cfgs.contains_key(parent) {
+                return Err(HsmGenError::generate(format!(
+                    "{} is in the Cfg state {}, only states without substates can be gated", state.name, parent
+                )))
+            }
+        }
+        if let Some(ref initial) = state.initial_transition {
+            if let Some(target) = hm.values().find(|x| x.id == initial.target_id && cfgs.contains_key(&x.name)) {
+                return Err(HsmGenError::generate(format!(
+                    "the initial state {} of {} is gated by Cfg", target.name, state.name
+                )))
             }
         }
+        Ok(())
+    }
+
+    /// The states the actions of `ca_vec` transition to
+    fn targets(ca_vec: &[CondAction], targets: &mut Vec<String>) {
+        for ca in ca_vec {
+            match ca.action {
+                Action::Transition { ref state, .. }         => targets.push(state.clone()),
+                Action::Diverge { ref cond_act_vec, .. }     => Self::targets(cond_act_vec, targets),
+                Action::Ignore | Action::Parent              => {},
+            }
+        }
+    }
+
+    /// The predicate of the Cfg state the arm of `ca_vec` transitions to. Its
+    /// arm is gated as a whole, so it may not lead anywhere else.
+    fn arm_cfg(ca_vec: &[CondAction], cfgs: &HashMap<String, String>) -> Result<Option<String>> {
+        let mut targets = Vec::new();
+        Self::targets(ca_vec, &mut targets);
+        let gated = match targets.iter().find(|x| cfgs.contains_key(*x)) {
+            Some(x) => x,
+            None    => return Ok(None)
+        };
+        if targets.iter().any(|x| x != gated) {
+            return Err(HsmGenError::generate(format!(
+                "the transition to the Cfg state {} shares its trigger with transitions to {}, which cannot be gated with it",
+                gated, targets.iter().filter(|x| *x != gated).cloned().collect::<Vec<_>>().join(", ")
+            )))
+        }
+        Ok(cfgs.get(gated).cloned())
+    }
+
+    fn create_enter_exit_arm(opt_func: &Option<String>, evt_type: &str, extra: Vec<String>) -> Option<String> {
+        if opt_func.is_none() && extra.is_empty() {
+            return None
+        }
+        let stmts = opt_func.iter().cloned().chain(extra).collect::<Vec<_>>();
+        Some(Self::arm(&[], &format!("hsm::Event::{}", evt_type), &Self::block(stmts, "hsm::Action::Ignore")))
     }
 
-    fn get_condaction_expr(cx: &ExtCtxt, ca: &CondAction, states: &Ident) -> (P<Expr>, bool) {
+    fn get_condaction_expr(ca: &CondAction) -> Result<(String, bool)> {
         let mut use_delayed_transition = true;
         let action = match ca.action {
             Action::Ignore                  => {
                 use_delayed_transition = false;
-                quote_expr!(&cx, hsm::Action::Ignore)
+                "hsm::Action::Ignore".to_string()
             },
             Action::Parent                  => {
                 use_delayed_transition = false;
-                quote_expr!(&cx, hsm::Action::Parent)
+                "hsm::Action::Parent".to_string()
             },
             Action::Transition { state: ref st_str, effect: ref opt_ef}  => {
-                let st = str_to_ident(st_str);
-                match opt_ef.as_ref().map(|x| str_to_ident(x)) {
-                    Some(ref ef) => quote_expr!(&cx, { $ef; $states::$st }),
-                    None         => quote_expr!(&cx, $states::$st)
+                let st = format!("States::{}", st_str);
+                match *opt_ef {
+                    Some(ref ef) => Self::block(vec!(ef.clone()), &st),
+                    None         => st
                 }
             },
             Action::Diverge { cond_act_vec: ref ca_vec, effect: ref opt_ef }     => {
-                let tupl = Self::create_action_expr(cx, ca_vec, states);
+                let tupl = try!(Self::create_action_expr(ca_vec));
                 use_delayed_transition = tupl.1;
-                match opt_ef.as_ref().map(|x| str_to_ident(x)) {
-                    Some(ref ef) => { let e = tupl.0; quote_expr!(&cx, { $ef; $e }) },
+                match *opt_ef {
+                    Some(ref ef) => Self::block(vec!(ef.clone()), &tupl.0),
                     None         => tupl.0
                 }
             }
         };
-        let effect = ca.effect.as_ref().map(|x| str_to_ident(x));
-        (
-            match effect {
-                Some(x) => quote_expr!(&cx, {
-                    $x;
-                    $action
-                }),
-                None    => quote_expr!(&cx, $action),
+        Ok((
+            match ca.effect {
+                Some(ref x) => Self::block(vec!(x.clone()), &action),
+                None        => action,
             },
             use_delayed_transition
-        )
+        ))
     }
 
-    fn create_action_expr(cx: &ExtCtxt, ca_vec: &Vec<CondAction>, states: &Ident) -> (P<Expr>, bool) {
+    fn create_action_expr(ca_vec: &[CondAction]) -> Result<(String, bool)> {
         match ca_vec.len() {
-            0 => panic!("Empty CondAction vector"),
+            0 => Err(HsmGenError::generate("Empty CondAction vector")),
             1 => {
                 let ca = &ca_vec[0];
                 if ca.guard.is_some() {
-                    panic!("CondAction vector with a single CondAction with a condition")
+                    Err(HsmGenError::generate("CondAction vector with a single CondAction with a condition"))
                 } else {
-                    Self::get_condaction_expr(cx, ca, states)
+                    Self::get_condaction_expr(ca)
                 }
             },
             2 => {
@@ -319,104 +399,143 @@ impl HsmGenerator {
                         } else {
                             if ca_vec[1].guard.as_ref().unwrap() == "else" {
                                 (&ca_vec[0],&ca_vec[1])
-                            } else { panic!("Condaction vector with 2 condactions, but neither condition is else") }
+                            } else { return Err(HsmGenError::generate("Condaction vector with 2 condactions, but neither condition is else")) }
                         }
-                    } else { panic!("Condaction vector with 2 condactions, but both dont have guards") };
-                let test_guard  = str_to_ident(ca_test.guard.as_ref().unwrap());
-                let (test_expr, test_use_delayed_transition) = Self::get_condaction_expr(cx, ca_test, states);
-                let (else_expr, else_use_delayed_transition) = Self::get_condaction_expr(cx, ca_else, states);
-                assert_eq!(test_use_delayed_transition, else_use_delayed_transition);
-                (
-                    quote_expr!(&cx, {
-                        if $test_guard {
-                            $test_expr
-                        } else {
-                            $else_expr
-                        }
-                    }),
+                    } else { return Err(HsmGenError::generate("Condaction vector with 2 condactions, but both dont have guards")) };
+                let test_guard  = ca_test.guard.as_ref().unwrap();
+                let (test_expr, test_use_delayed_transition) = try!(Self::get_condaction_expr(ca_test));
+                let (else_expr, else_use_delayed_transition) = try!(Self::get_condaction_expr(ca_else));
+                if test_use_delayed_transition != else_use_delayed_transition {
+                    return Err(HsmGenError::generate("Condaction vector mixes transitions with ignore/parent actions"))
+                }
+                Ok((
+                    format!("if {} {{\n{}\n}} else {{\n{}\n}}", test_guard, Self::indent(&test_expr), Self::indent(&else_expr)),
                     test_use_delayed_transition
-                )
+                ))
             },
-            _ => panic!("CondAction vector with more than 2 condactions is not supported")
+            _ => Err(HsmGenError::generate("CondAction vector with more than 2 condactions is not supported"))
         }
     }
 
-    fn create_final_action_expr(cx: &ExtCtxt, ca_vec: &Vec<CondAction>, states: &Ident) -> P<Expr> {
-        let (expr, use_delayed_transition) = Self::create_action_expr(&cx, ca_vec, states);
-        match use_delayed_transition {
-            true  => quote_expr!(&cx, hsm_delayed_transition!(probe, { $expr })),
+    fn create_final_action_expr(ca_vec: &[CondAction]) -> Result<String> {
+        let (expr, use_delayed_transition) = try!(Self::create_action_expr(ca_vec));
+        Ok(match use_delayed_transition {
+            true  => format!("hsm_delayed_transition!(probe, {{\n{}\n}})", Self::indent(&expr)),
             false => expr
+        })
+    }
+
+    /// The pattern of the `Events` variant of a signal, binding its attributes
+    /// by name for guards and effects
+    fn signal_pattern(name: &str, attributes: &[SignalAttribute]) -> String {
+        let bindings = attributes.iter().map(|a| format!("ref {}", a.name)).collect::<Vec<_>>().join(", ");
+        match attributes.len() {
+            0 => name.to_string(),
+            1 => format!("{}({})", name, bindings),
+            _ => format!("{} {{ {} }}", name, bindings),
+        }
+    }
+
+    /// Checks the conditions of the change events of a state in turn, the
+    /// first one that holds gives the action, `fallback` when none does
+    fn create_change_expr(changes: &[(&String, &Vec<CondAction>)], fallback: &str) -> Result<String> {
+        let mut expr = String::new();
+        for &(condition, ca_vec) in changes.iter() {
+            let action = try!(Self::create_final_action_expr(ca_vec));
+            expr.push_str(&format!("if {} {{\n{}\n}} else ", condition, Self::indent(&action)));
         }
+        expr.push_str(&format!("{{\n{}\n}}", Self::indent(fallback)));
+        Ok(expr)
     }
 
-    fn create_state_impl(&mut self, state: &State, events: &Ident, states: &Ident, shr_dat: &Ident, timeout: &Ident) -> P<Item> {
-        let cx = self.extctxt();
-        let state_ident = str_to_ident(state.name.as_str());
-        let mut arms: Vec<Arm> = Vec::new();
+    fn create_state_impl(state: &State, cfgs: &HashMap<String, String>) -> Result<Vec<String>> {
+        let mut arms = Vec::new();
         let mut entry_extra = Vec::new();
         let mut exit_extra = Vec::new();
+        let mut changes = Vec::new();
         let mut actions_vec = state.actions.iter().collect::<Vec<(&Event, &Vec<CondAction>)>>();
         actions_vec.sort_by(|a,b| a.0.cmp(b.0));
         for (evt, ca_vec) in actions_vec {
+            let gate = try!(Self::arm_cfg(ca_vec, cfgs));
+            match (evt, &gate) {
+                (&Event::Time {..}, _) | (&Event::Signal {..}, _) | (_, &None) => {},
+                (_, &Some(_)) => return Err(HsmGenError::generate(
+                    "only transitions triggered by signal and time events can lead to a Cfg state"
+                )),
+            }
             let pat = match *evt {
-                Event::Time {ref name, ref relative, ref timeout_ms, ..} => {
-                    let nam = str_to_ident(name);
-                    entry_extra.push(
-                        quote_expr!(&cx,
-                            shr.timer.start($timeout::$nam, $timeout_ms);
-                        )
-                    );
-                    exit_extra.push(
-                        quote_expr!(&cx,
-                            shr.timer.stop($timeout::$nam);
-                        )
-                    );
-                    quote_pat!(&cx, hsm::Event::User($events::$timeout($timeout::$nam)))
+                Event::Time {ref name, ref timeout_ms, ..} => {
+                    entry_extra.push(format!("shr.timer.start(Timeout::{}, {})", name, timeout_ms));
+                    exit_extra.push(format!("shr.timer.stop(Timeout::{})", name));
+                    format!("hsm::Event::User(Events::Timeout(Timeout::{}))", name)
                 },
-                Event::Signal {ref name, ..} => {
-                    let nam = str_to_ident(name);
-                    quote_pat!(&cx, hsm::Event::User($events::$nam))
+                Event::Signal {ref name, ref attributes, ..} => {
+                    format!("hsm::Event::User(Events::{})", Self::signal_pattern(name, attributes))
+                },
+                Event::Change {ref condition, ..} => {
+                    changes.push((condition, ca_vec));
+                    continue
                 },
                 Event::Any | Event::UserAny => continue
             };
-            arms.push(cx.arm(DUMMY_SP,
-                             vec!(pat),
-                             Self::create_final_action_expr(&cx, ca_vec, states)
-            ));
+            // Left out with the state it leads to
+            let mut attrs = Self::arm_comments(ca_vec);
+            attrs.extend(gate.iter().map(|pred| format!("#[cfg({})]", pred)));
+            arms.push(Self::arm(&attrs, &pat, &try!(Self::create_final_action_expr(ca_vec))));
         };
         let mut ordered_arms = Vec::new();
-        Self::create_enter_exit_arm(&cx, &state.entry, "Enter", entry_extra).map(|x| ordered_arms.push(x));
-        Self::create_enter_exit_arm(&cx, &state.exit, "Exit", exit_extra).map(|x| ordered_arms.push(x));
+        ordered_arms.extend(Self::create_enter_exit_arm(&state.entry, "Enter", entry_extra));
+        ordered_arms.extend(Self::create_enter_exit_arm(&state.exit, "Exit", exit_extra));
         ordered_arms.extend(arms);
-        if let Some(ref ca_vec) = state.actions.get(&Event::UserAny) {
-            ordered_arms.push(cx.arm(DUMMY_SP,
-                             vec!(quote_pat!(&cx, hsm::Event::User(_))),
-                             Self::create_final_action_expr(&cx, ca_vec, states)
-            ))
+        if !changes.is_empty() {
+            ordered_arms.push(Self::arm(&[], "hsm::Event::User(Events::Poll)",
+                                        &try!(Self::create_change_expr(&changes, "hsm::Action::Parent"))));
+        }
+        if let Some(ca_vec) = state.actions.get(&Event::UserAny) {
+            ordered_arms.push(Self::arm(&Self::arm_comments(ca_vec), "hsm::Event::User(_)", &try!(Self::create_final_action_expr(ca_vec))));
         }
         match state.actions.get(&Event::Any) {
-            Some(ref ca_vec) => ordered_arms.push(cx.arm(DUMMY_SP,
-                             vec!(quote_pat!(&cx, _)),
-                             Self::create_final_action_expr(&cx, ca_vec, states)
-            )),
-            None => ordered_arms.push(cx.arm(DUMMY_SP,
-                             vec!(quote_pat!(&cx, _)),
-                             quote_expr!(&cx, hsm::Action::Parent)
-            ))
+            Some(ca_vec)     => ordered_arms.push(Self::arm(&Self::arm_comments(ca_vec), "_", &try!(Self::create_final_action_expr(ca_vec)))),
+            None             => ordered_arms.push(Self::arm(&[], "_", "hsm::Action::Parent")),
         }
-        let match_expr = cx.expr_match(DUMMY_SP, quote_expr!(&cx, *evt), ordered_arms);
-        quote_item!(&cx,
-            impl hsm::State<$events, $states, $shr_dat> for $state_ident {
-                fn handle_event(&mut self, shr: &mut $shr_dat, evt: &hsm::Event<$events>, probe: bool) -> hsm::Action<$states> {
-                    $match_expr
-                }
-            }
-        ).unwrap()
+        let match_expr = format!("match *evt {{\n{}\n}}", Self::indent(&ordered_arms.join("\n")));
+        // Conditions may have changed with an event the state handled itself.
+        // An event passed on with Parent is handled after this returns, what
+        // the parent changes is checked on the next Poll.
+        let body = if changes.is_empty() {
+            match_expr
+        } else {
+            let check = try!(Self::create_change_expr(&changes, "hsm::Action::Ignore"));
+            format!("let action = {};\nif let hsm::Event::User(_) = *evt {{\n{}\n}}\naction",
+                    match_expr,
+                    Self::indent(&format!("if let hsm::Action::Ignore = action {{\n{}\n}}",
+                                          Self::indent(&format!("return {};", check)))))
+        };
+        let item = format!(
+            "impl hsm::State<Events, States, SharedData> for {} {{\n{}\n}}",
+            state.name,
+            Self::indent(&format!(
+                "fn handle_event(&mut self, shr: &mut SharedData, evt: &hsm::Event<Events>, probe: bool) -> hsm::Action<States> {{\n{}\n}}",
+                Self::indent(&body)
+            ))
+        );
+        Ok(match state.cfg {
+            // Without the feature the state is never entered, its declaration
+            // still needs an impl
+            Some(ref pred) => vec!(
+                format!("#[cfg({})]\n{}", pred, item),
+                format!(
+                    "#[cfg(not({}))]\nimpl hsm::State<Events, States, SharedData> for {} {{\n{}\n}}",
+                    pred,
+                    state.name,
+                    Self::indent("fn handle_event(&mut self, _: &mut SharedData, _: &hsm::Event<Events>, _: bool) -> hsm::Action<States> {\n    hsm::Action::Parent\n}")
+                )
+            ),
+            None           => vec!(item)
+        })
     }
 
-    pub fn create_function_stubs(&mut self, hm: &HashMap<String, State>) {
-        let events = str_to_ident("Events");
-        let shr_dat= str_to_ident("SharedData");
+    pub fn create_function_stubs(&mut self, hm: &HashMap<String, State>) -> Result<()> {
         let mut functions = HashSet::new();
         hm.values().map(|s| {
             s.entry.as_ref().map(|x|
@@ -426,21 +545,14 @@ impl HsmGenerator {
                 functions.insert(x.to_string())
             )
         }).count();
+        let mut functions = functions.into_iter().collect::<Vec<_>>();
+        functions.sort();
         for func in functions {
-            let it = {
-                let cx = self.extctxt();
-                let func_ident  = str_to_ident(&func);
-                quote_item!(&cx,
-                    fn $func_ident(shr_data: &mut $shr_dat, evt: hsm::Event<$events>) {
-                        unimplemented!();
-                    }
-                ).unwrap().map(|mut x| {
-                    x.vis = Visibility::Public;
-                    x
-                })
-            };
-            self.krate.module.items.push(it);
-            // println!("{}", func);
+            self.items.push(format!(
+                "{}fn {}(shr_data: &mut SharedData, evt: hsm::Event<Events>) {{\n    unimplemented!();\n}}",
+                self.visibility(), func
+            ));
         }
+        Ok(())
     }
 }
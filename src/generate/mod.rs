@@ -22,6 +22,5 @@
  * SOFTWARE.
  */
 mod gen;
-mod inner;
 
 pub use self::gen::HsmGenerator;
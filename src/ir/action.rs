@@ -22,6 +22,7 @@
  * SOFTWARE.
  */
 use std::collections::HashMap;
+use ::error::{HsmGenError, Result};
 use super::{Transition, Subvertex, CondAction, State};
 
 
@@ -34,13 +35,22 @@ pub enum Action {
 }
 
 impl Action {
-    pub fn from_transition(t: &Transition, sm: &HashMap<String, State>, vm: &HashMap<String, Subvertex>) -> Self {
+    pub fn from_transition(t: &Transition, sm: &HashMap<String, State>, vm: &HashMap<String, Subvertex>) -> Result<Self> {
         Self::from_transition_with_effect(t, sm, vm, None)
     }
 
-    fn from_transition_with_effect(t: &Transition, sm: &HashMap<String, State>, vm: &HashMap<String, Subvertex>, effect: Option<String>) -> Self {
-        assert!(t.guard.is_none());
-        assert!(t.trigger.is_none());
+    fn from_transition_with_effect(t: &Transition, sm: &HashMap<String, State>, vm: &HashMap<String, Subvertex>, effect: Option<String>) -> Result<Self> {
+        let err = |msg: &str| Err(HsmGenError::model(msg)
+                                    .context("uml:Transition", t.id.clone())
+                                    .with_location(t.location.clone()));
+        // The trigger and guard of the transition leaving a state are taken
+        // by its CondAction, ones left over are on transitions out of pseudostates
+        if t.trigger.is_some() {
+            return err("Transition out of a pseudostate with a trigger")
+        }
+        if t.guard.is_some() {
+            return err("Transition out of an initial state or junction with a guard")
+        }
 
         let eff = match (effect, &t.effect) {
             (Some(ref a), &Some(ref b)) => Some(format!("{{{};{}}}", a, b)),
@@ -51,22 +61,28 @@ impl Action {
         if let Some(state) = sm.get(&t.target_id) {
             match state.initial_transition {
                 Some(ref trans) => Self::from_transition_with_effect(trans, sm, vm, eff),
-                None            => Action::Transition { state: state.name.clone(), effect: eff }
+                None            => Ok(Action::Transition { state: state.name.clone(), effect: eff })
             }
         } else {
             if let Some(subvertex) = vm.get(&t.target_id) {
                 match *subvertex {
-                    Subvertex::Initial  {..}                   => panic!("Transition to initial state is forbidden"),
-                    Subvertex::Final    {..}                   => panic!("Implement transition to final state"),
-                    Subvertex::State    {ref state, ..}        => panic!("CondAction get_target: found state in subvertex map"),
-                    Subvertex::Junction {ref transition, ..}   => Self::from_transition_with_effect(&transition, sm, vm, eff),
-                    Subvertex::Choice   {ref transitions, ..}  => Action::Diverge {
-                        cond_act_vec: transitions.iter().map(|x| CondAction::from_transition((*x).clone(), sm, vm)).collect(),
-                        effect:       eff
+                    Subvertex::Initial  {..}                   => err("Transition to initial state is forbidden"),
+                    Subvertex::Final    {..}                   => err("Transition to final state is not implemented"),
+                    Subvertex::State    {ref state, ..}        => err("Transition target state found in subvertex map"),
+                    Subvertex::Junction {ref transition, ..}   => Self::from_transition_with_effect(transition, sm, vm, eff),
+                    Subvertex::Choice   {ref transitions, ..}  => {
+                        let mut cond_act_vec = Vec::new();
+                        for x in transitions.iter() {
+                            cond_act_vec.push(try!(CondAction::from_transition((*x).clone(), sm, vm)));
+                        }
+                        Ok(Action::Diverge {
+                            cond_act_vec: cond_act_vec,
+                            effect:       eff
+                        })
                     },
                 }
             } else {
-                panic!("CondAction get_target for {:?}: target_id {} not in subvertex map", t, t.target_id)
+                err(&format!("Transition target_id {} not in subvertex map", t.target_id))
             }
         }
     }
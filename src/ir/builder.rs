@@ -0,0 +1,263 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use std::collections::HashMap;
+
+use ::error::{HsmGenError, Result, Diagnostics};
+use ::location::SourceLocation;
+use super::{State, Subvertex, Transition, Event, CondAction, Action};
+
+
+/// Turns the transitions of every state into actions and keys the map by state name
+pub fn resolve_states(mut sm: HashMap<String, State>, mut vm: HashMap<String, Subvertex>, diag: &Diagnostics) -> Result<HashMap<String, State>> {
+    try!(check_pseudostate_transitions(&mut sm, &mut vm, diag));
+    let vm = &vm;
+    // Convert transitions to condactions
+    for key in sm.keys().map(|x| x.to_string()).collect::<Vec<String>>().iter() {
+        let mut state = sm.get(key).unwrap().clone();
+        while let Some(trans) = state.transitions.pop() {
+            if let Err(e) = state.add_action(trans, &sm, vm) {
+                try!(diag.recover(e));
+            }
+        }
+        sm.insert(key.to_string(), state);
+    }
+    // debug!("{:#?}", sm);
+    // Replace hashmap keys with state names, the generated types are named
+    // after them so they must be unique
+    let mut states = sm.into_iter().collect::<Vec<_>>();
+    states.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut by_name: HashMap<String, State> = HashMap::new();
+    for (id, state) in states {
+        if let Some(first) = by_name.get(&state.name) {
+            try!(diag.recover(HsmGenError::model(format!("state name {} is also used by state {}", state.name, first.id))
+                                .context("State", id)
+                                .with_location(state.location.clone())));
+            continue
+        }
+        by_name.insert(state.name.clone(), state);
+    }
+    // debug!("{:#?}", by_name);
+    Ok(by_name)
+}
+
+/// Joins several pieces of code into one block, None if there are none
+pub fn join_code(parts: Vec<String>) -> Option<String> {
+    let mut parts = parts.into_iter().filter(|x| !x.trim().is_empty()).collect::<Vec<String>>();
+    match parts.len() {
+        0 => None,
+        1 => parts.pop(),
+        _ => Some(format!("{{{}}}", parts.join(";")))
+    }
+}
+
+fn transition_error(t: &Transition, message: String) -> HsmGenError {
+    HsmGenError::model(message).context("Transition", t.id.clone()).with_location(t.location.clone())
+}
+
+/// Pseudostates pass on the event that led to them, so only transitions out
+/// of states have a trigger and only those out of states and choices a guard.
+/// Others are reported and read without them.
+fn check_pseudostate_transitions(sm: &mut HashMap<String, State>, vm: &mut HashMap<String, Subvertex>, diag: &Diagnostics) -> Result<()> {
+    let mut states = sm.iter_mut().collect::<Vec<_>>();
+    states.sort_by(|a, b| a.0.cmp(b.0));
+    for (_, state) in states {
+        if let Some(ref mut t) = state.initial_transition {
+            try!(check_pseudostate_transition(t, "an initial state", diag));
+        }
+    }
+    let mut subvertices = vm.iter_mut().collect::<Vec<_>>();
+    subvertices.sort_by(|a, b| a.0.cmp(b.0));
+    for (_, subvertex) in subvertices {
+        match *subvertex {
+            Subvertex::Junction {ref mut transition, ..}  => try!(check_pseudostate_transition(transition, "a junction", diag)),
+            Subvertex::Choice   {ref mut transitions, ..} => for t in transitions.iter_mut() {
+                if t.trigger.is_some() {
+                    try!(diag.recover(transition_error(t, "a transition out of a choice cannot have a trigger".to_string())));
+                    t.trigger = None;
+                }
+            },
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn check_pseudostate_transition(t: &mut Transition, source: &str, diag: &Diagnostics) -> Result<()> {
+    if t.trigger.is_some() {
+        try!(diag.recover(transition_error(t, format!("a transition out of {} cannot have a trigger", source))));
+        t.trigger = None;
+    }
+    if t.guard.is_some() {
+        try!(diag.recover(transition_error(t, format!("a transition out of {} cannot have a guard", source))));
+        t.guard = None;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum VertexKind {
+    Initial,
+    Final,
+    Junction,
+    Choice,
+}
+
+#[derive(Debug, Clone)]
+struct Vertex {
+    kind     : VertexKind,
+    parent   : Option<String>,
+    location : Option<SourceLocation>,
+}
+
+/// Collects the states, pseudostates and transitions of a model by id, for
+/// readers of formats that are not XMI, and resolves them into the same
+/// state map `XmiReader::read_states` returns.
+///
+/// The `parent` of a state added to the builder is the id of its parent,
+/// `build` replaces it with the parent's name.
+#[derive(Debug, Default)]
+pub struct ModelBuilder {
+    states      : Vec<State>,
+    vertices    : HashMap<String, Vertex>,
+    transitions : Vec<Transition>,
+    internal    : Vec<(String, Event, CondAction)>,
+}
+
+impl ModelBuilder {
+    pub fn new() -> Self {
+        ModelBuilder::default()
+    }
+
+    pub fn add_state(&mut self, state: State) {
+        self.states.push(state);
+    }
+
+    pub fn has_state(&self, id: &str) -> bool {
+        self.states.iter().any(|x| x.id == id)
+    }
+
+    pub fn state_mut(&mut self, id: &str) -> Option<&mut State> {
+        self.states.iter_mut().find(|x| x.id == id)
+    }
+
+    pub fn add_vertex(&mut self, id: &str, kind: VertexKind, parent: Option<String>, location: Option<SourceLocation>) {
+        self.vertices.insert(id.to_string(), Vertex { kind: kind, parent: parent, location: location });
+    }
+
+    pub fn vertex_kind(&self, id: &str) -> Option<VertexKind> {
+        self.vertices.get(id).map(|x| x.kind)
+    }
+
+    pub fn add_transition(&mut self, transition: Transition) {
+        self.transitions.push(transition);
+    }
+
+    /// A transition without target, its effect runs without leaving the state
+    pub fn add_internal(&mut self, state_id: &str, event: Event, guard: Option<String>, effect: Option<String>) {
+        self.internal.push((state_id.to_string(), event, CondAction {
+            doc    : None,
+            guard  : guard,
+            effect : effect,
+            action : Action::Ignore,
+        }));
+    }
+
+    pub fn build(self, diag: &Diagnostics) -> Result<HashMap<String, State>> {
+        let names = self.states.iter().map(|x| (x.id.clone(), x.name.clone())).collect::<HashMap<String, String>>();
+        let mut sm = HashMap::new();
+        for mut state in self.states.into_iter() {
+            state.parent = match state.parent.take() {
+                Some(p_id) => match names.get(&p_id) {
+                    Some(name) => Some(name.clone()),
+                    None       => return Err(HsmGenError::model(format!("parent {} is not a state", p_id))
+                                                .context("State", state.id.clone())
+                                                .with_location(state.location.clone()))
+                },
+                None       => None
+            };
+            sm.insert(state.id.clone(), state);
+        }
+        let mut junctions: HashMap<String, Vec<Transition>> = HashMap::new();
+        for t in self.transitions.into_iter() {
+            if !sm.contains_key(&t.target_id) && !self.vertices.contains_key(&t.target_id) {
+                try!(diag.recover(transition_error(&t, format!("target {} is not a state or pseudostate", t.target_id))));
+                continue
+            }
+            if let Some(state) = sm.get_mut(&t.source_id) {
+                state.transitions.push(t);
+                continue
+            }
+            match self.vertices.get(&t.source_id).map(|x| (x.kind, x.parent.clone())) {
+                Some((VertexKind::Initial, Some(p_id))) => match sm.get_mut(&p_id) {
+                    Some(parent) => {
+                        if parent.initial_transition.is_some() {
+                            try!(diag.recover(transition_error(&t, format!("state {} has more than one initial transition", parent.name))));
+                        } else {
+                            parent.initial_transition = Some(t);
+                        }
+                    },
+                    None         => try!(diag.recover(transition_error(&t, format!("initial state in {} which is not a state", p_id)))),
+                },
+                // The top level initial transition is taken by the hsm at runtime
+                Some((VertexKind::Initial, None))        => {
+                    let mut t = t;
+                    try!(check_pseudostate_transition(&mut t, "an initial state", diag));
+                },
+                Some((VertexKind::Final, _))             => try!(diag.recover(transition_error(&t, "transition from a final state".to_string()))),
+                Some((VertexKind::Junction, _)) |
+                Some((VertexKind::Choice, _))            => junctions.entry(t.source_id.clone()).or_insert(Vec::new()).push(t),
+                None                                     => try!(diag.recover(transition_error(&t, format!("source {} is not a state or pseudostate", t.source_id)))),
+            }
+        }
+        let mut vm = HashMap::new();
+        for (id, vertex) in self.vertices.into_iter() {
+            let mut outgoing = junctions.remove(&id).unwrap_or(Vec::new());
+            let subvertex = match vertex.kind {
+                VertexKind::Initial  => Subvertex::Initial { id: id.clone() },
+                VertexKind::Final    => Subvertex::Final   { id: id.clone() },
+                VertexKind::Choice   => Subvertex::Choice  { id: id.clone(), transitions: outgoing },
+                VertexKind::Junction => {
+                    if outgoing.len() != 1 {
+                        try!(diag.recover(HsmGenError::model(format!("junction has {} outgoing transitions, expected 1", outgoing.len()))
+                                            .context("Junction", id.clone())
+                                            .with_location(vertex.location)));
+                        continue
+                    }
+                    Subvertex::Junction { id: id.clone(), transition: outgoing.pop().unwrap() }
+                },
+            };
+            vm.insert(id, subvertex);
+        }
+        // Internal transitions come after the transitions of the state that leave it
+        let mut sm = try!(resolve_states(sm, vm, diag));
+        for (state_id, evt, ca) in self.internal.into_iter() {
+            let name = names.get(&state_id).cloned().unwrap_or(state_id.clone());
+            match sm.get_mut(&name) {
+                Some(state) => state.actions.entry(evt).or_insert(Vec::new()).push(ca),
+                None        => try!(diag.recover(HsmGenError::model(format!("internal transition in {} which is not a state", state_id)))),
+            }
+        }
+        Ok(sm)
+    }
+}
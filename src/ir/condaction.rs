@@ -22,23 +22,32 @@
  * SOFTWARE.
  */
 use std::collections::HashMap;
+use ::error::{HsmGenError, Result};
 use super::{Transition, Subvertex, Action, State};
 
 
 #[derive(Debug, Clone)]
 pub struct CondAction {
+    pub doc:    Option<String>,
     pub guard:  Option<String>,
     pub effect: Option<String>,
     pub action: Action
 }
 
 impl CondAction {
-    pub fn from_transition(mut t: Transition, sm: &HashMap<String, State>, vm: &HashMap<String, Subvertex>) -> Self {
-        assert!(t.trigger.is_none());
-        CondAction {
-            guard:  t.guard.take(),
-            effect: t.effect.take(),
-            action: Action::from_transition(&t, sm, vm),
+    pub fn from_transition(mut t: Transition, sm: &HashMap<String, State>, vm: &HashMap<String, Subvertex>) -> Result<Self> {
+        if t.trigger.is_some() {
+            return Err(HsmGenError::model("Transition out of a choice with a trigger")
+                            .context("uml:Transition", t.id)
+                            .with_location(t.location))
         }
+        let guard  = t.guard.take();
+        let effect = t.effect.take();
+        Ok(CondAction {
+            doc:    t.doc.take(),
+            guard:  guard,
+            effect: effect,
+            action: try!(Action::from_transition(&t, sm, vm)),
+        })
     }
 }
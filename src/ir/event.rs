@@ -21,42 +21,289 @@
  * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
  * SOFTWARE.
  */
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
 use sxd_xpath::nodeset::Node;
-use ::XmiReader;
+use ::xmi::XmiIndex;
+use ::error::{HsmGenError, Result};
+use ::location::SourceLocation;
 
 
-#[derive(Debug, Eq, PartialEq, Hash, Clone, Ord, PartialOrd)]
+/// An event, equal to another one with the same fields whatever their
+/// locations, which only point at one of the triggers using it
+#[derive(Debug, Clone)]
 pub enum Event {
-    Time   {id: String, name: String, relative: bool, timeout_ms: u32},
-    Signal {id: String, name: String},
+    Time   {id: String, name: String, relative: bool, timeout_ms: u32, location: Option<SourceLocation>},
+    Signal {id: String, name: String, attributes: Vec<SignalAttribute>, doc: Option<String>, location: Option<SourceLocation>},
+    /// Fires when `condition` holds, checked after the events a state handles
+    /// itself and on `Events::Poll`
+    Change {id: String, condition: String, location: Option<SourceLocation>},
     UserAny,
     Any,
 }
 
+/// The fields of an `Event` that tell it apart
+#[derive(Eq, PartialEq, Hash, Ord, PartialOrd)]
+enum EventKey<'e> {
+    Time   {id: &'e str, name: &'e str, relative: bool, timeout_ms: u32},
+    Signal {id: &'e str, name: &'e str, attributes: &'e [SignalAttribute], doc: Option<&'e str>},
+    Change {id: &'e str, condition: &'e str},
+    UserAny,
+    Any,
+}
+
+impl PartialEq for Event {
+    fn eq(&self, other: &Event) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Event {}
+
+impl Hash for Event {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state)
+    }
+}
+
+impl PartialOrd for Event {
+    fn partial_cmp(&self, other: &Event) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Event {
+    fn cmp(&self, other: &Event) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// An attribute of the signal of a signal event, carried by its `Events` variant
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Ord, PartialOrd)]
+pub struct SignalAttribute {
+    pub name : String,
+    pub ty   : String,
+}
+
 impl Event {
-    pub fn from_xml(reader: &XmiReader, node: Node) -> Self {
-        match reader.get_attr(node, "type").unwrap().as_str() {
-            "uml:TimeEvent"       => Event::Time {
-                id:         reader.get_attr(node, "id").unwrap(),
-                name:       reader.get_attr(node, "name").expect("TimeEvent with no name"),
-                relative:   match reader.get_attr(node, "isRelative").expect("TimeEvent without isRelative").as_str() {
-                    "true"  => true,
-                    "false" => false,
-                    _ => panic!("Relative with unknown value")
-                },
-                timeout_ms: u32::from_str_radix(
-                    reader.get_attr(get_node!(reader, node, "when/expr"), "value")
-                        .expect("TimeEvent without timeout")
-                        .as_str(),
-                    10
-                ).expect("Event timeout_ms from_str_radix")
+    fn key<'e>(&'e self) -> EventKey<'e> {
+        match *self {
+            Event::Time {ref id, ref name, relative, timeout_ms, ..}   => EventKey::Time {id: id, name: name, relative: relative, timeout_ms: timeout_ms},
+            Event::Signal {ref id, ref name, ref attributes, ref doc, ..} => EventKey::Signal {id: id, name: name, attributes: attributes, doc: doc.as_ref().map(|x| x.as_str())},
+            Event::Change {ref id, ref condition, ..}                  => EventKey::Change {id: id, condition: condition},
+            Event::UserAny                                             => EventKey::UserAny,
+            Event::Any                                                 => EventKey::Any,
+        }
+    }
+
+    pub fn from_xml(reader: &XmiIndex, node: Node) -> Result<Self> {
+        match try!(reader.req_type(node)).as_str() {
+            "uml:TimeEvent"       => {
+                // EMF based exporters leave out attributes that have their default value
+                let relative = match reader.get_attr(node, "isRelative").as_ref().map(|x| x.as_str()) {
+                    Some("true")           => true,
+                    Some("false") | None   => false,
+                    Some(x)                => return Err(reader.error(node, format!("isRelative with unknown value {:?}", x)))
+                };
+                let expr_node = get_node!(reader, node, "when/expr");
+                let timeout = match reader.get_attr(expr_node, "value") {
+                    Some(x) => x,
+                    None    => match try!(reader.body(expr_node)) {
+                        Some(x) => x.trim().to_string(),
+                        None    => return Err(reader.error(expr_node, "TimeEvent without timeout"))
+                    }
+                };
+                Ok(Event::Time {
+                    id:         try!(reader.req_attr(node, "id")),
+                    name:       try!(reader.name_attr(node)),
+                    relative:   relative,
+                    timeout_ms: try!(u32::from_str_radix(timeout.as_str(), 10).map_err(|_|
+                        reader.error(node, format!("timeout {:?} is not a number of milliseconds", timeout))
+                    )),
+                    location:   reader.location(node),
+                })
+            },
+            "uml:SignalEvent"     => {
+                let signal = try!(Event::signal_node(reader, node));
+                Ok(Event::Signal {
+                    id:         try!(reader.req_attr(node, "id")),
+                    name:       try!(reader.name_attr(node)),
+                    attributes: match signal {
+                        Some(signal) => try!(Event::signal_attributes(reader, signal)),
+                        None         => Vec::new()
+                    },
+                    // Modelers document the signal, or else the event
+                    doc:        match signal {
+                        Some(signal) => try!(reader.comments(signal)),
+                        None         => None
+                    }.or(try!(reader.comments(node))),
+                    location:   reader.location(node),
+                })
             },
-            "uml:SignalEvent"     => Event::Signal {
-                id:         reader.get_attr(node, "id").unwrap(),
-                name:       reader.get_attr(node, "name").expect("SignalEvent with no name")
+            "uml:ChangeEvent"     => {
+                let expr_node = match get_node_opt!(reader, node, "changeExpression") {
+                    Some(x) => x,
+                    None    => return Err(reader.error(node, "ChangeEvent without changeExpression"))
+                };
+                let condition = match reader.get_attr(expr_node, "value") {
+                    Some(x) => x,
+                    None    => match try!(reader.body(expr_node)) {
+                        Some(x) => x.trim().to_string(),
+                        None    => return Err(reader.error(expr_node, "ChangeEvent without condition"))
+                    }
+                };
+                Ok(Event::Change {
+                    id:         try!(reader.req_attr(node, "id")),
+                    condition:  condition,
+                    location:   reader.location(node),
+                })
             },
-            "uml:AnyReceiveEvent" => Event::Any,
-            _ => panic!("Event with unknown type")
+            "uml:AnyReceiveEvent" => Ok(Event::Any),
+            _ => Err(reader.error(node, "Event with unknown type"))
         }
     }
+
+    /// The signal a signal event refers to
+    fn signal_node<'d>(reader: &XmiIndex<'d>, node: Node<'d>) -> Result<Option<Node<'d>>> {
+        let signal_id = match try!(reader.get_ref(node, "signal")) {
+            Some(x) => x,
+            None    => return Ok(None)
+        };
+        match try!(reader.element_by_id(&signal_id)) {
+            Some(x) => Ok(Some(x)),
+            None    => Err(reader.error(node, format!("signal event refers to unknown signal {}", signal_id)))
+        }
+    }
+
+    /// The owned attributes of `signal`
+    fn signal_attributes(reader: &XmiIndex, signal: Node) -> Result<Vec<SignalAttribute>> {
+        let mut attributes = Vec::new();
+        for attr in get_ns!(reader, signal, "ownedAttribute").iter() {
+            let type_ref = match try!(reader.get_ref(attr, "type")) {
+                Some(x) => x,
+                None    => return Err(reader.error(attr, "signal attribute without a type"))
+            };
+            // Types of the standard libraries are referred to like
+            // pathmap://UML_LIBRARIES/UMLPrimitiveTypes.library.uml#Integer
+            let ty = if type_ref.starts_with("pathmap:") {
+                type_ref.rsplit('#').next().unwrap_or("").to_string()
+            } else {
+                match try!(reader.element_by_id(&type_ref)) {
+                    Some(ty_node) => try!(reader.name_attr(ty_node)),
+                    None          => return Err(reader.error(attr, format!("signal attribute of unknown type {}", type_ref)))
+                }
+            };
+            // The RustType stereotype gives the Rust type path of an attribute
+            let ty = match reader.stereotype(attr, "RustType").and_then(|app| reader.get_attr(app, "path")) {
+                Some(path) => path,
+                None       => rust_type(&ty)
+            };
+            attributes.push(SignalAttribute {
+                name : try!(reader.name_attr(attr)),
+                ty   : ty,
+            });
+        }
+        Ok(attributes)
+    }
+
+    /// A signal of a model without event elements. These are told apart by
+    /// name, the same trigger on several transitions is one event.
+    pub fn signal(name: &str) -> Self {
+        Event::Signal {
+            id         : name.to_string(),
+            name       : name.to_string(),
+            attributes : Vec::new(),
+            doc        : None,
+            location   : None,
+        }
+    }
+
+    /// A relative time event of `state`, named like `StateAfter5000ms`
+    pub fn after(state: &str, timeout_ms: u32, location: Option<SourceLocation>) -> Self {
+        Event::Time {
+            id         : format!("{}After{}ms", state, timeout_ms),
+            name       : format!("{}After{}ms", state, timeout_ms),
+            relative   : true,
+            timeout_ms : timeout_ms,
+            location   : location,
+        }
+    }
+
+    /// A change event of a model without event elements, told apart by condition
+    pub fn change(condition: &str, location: Option<SourceLocation>) -> Self {
+        Event::Change {
+            id        : format!("when({})", condition),
+            condition : condition.to_string(),
+            location  : location,
+        }
+    }
+
+    /// The event a trigger of a text diagram stands for. `after 5 s` (or
+    /// `after(5 s)`) in `state` is a relative time event named like
+    /// `StateAfter5000ms`, `when(expr)` a change event, `*` is any event and
+    /// everything else is a signal named by the trigger.
+    pub fn from_trigger(trigger: &str, state: &str, location: Option<SourceLocation>) -> Result<Self> {
+        let trigger = trigger.trim();
+        if trigger == "*" {
+            return Ok(Event::Any)
+        }
+        if trigger.starts_with("when") && !trigger[4..].starts_with(|c: char| c.is_alphanumeric() || c == '_') {
+            let condition = trigger[4..].trim();
+            let condition = if condition.starts_with('(') && condition.ends_with(')') {
+                &condition[1..condition.len() - 1]
+            } else {
+                condition
+            };
+            if condition.trim().is_empty() {
+                return Err(HsmGenError::model(format!("no condition in {:?}", trigger)).with_location(location))
+            }
+            return Ok(Event::change(condition.trim(), location))
+        }
+        if trigger.starts_with("after") && !trigger[5..].starts_with(|c: char| c.is_alphanumeric() || c == '_') {
+            let timeout = trigger[5..].trim().trim_start_matches('(').trim_end_matches(')');
+            return match parse_duration_ms(timeout) {
+                Some(ms) => Ok(Event::after(state, ms, location)),
+                None     => Err(HsmGenError::model(format!("invalid timeout in {:?}", trigger)).with_location(location))
+            }
+        }
+        Ok(Event::signal(trigger))
+    }
+}
+
+/// The Rust type for a UML primitive type, other types keep their name
+fn rust_type(uml: &str) -> String {
+    match uml {
+        "Boolean"          => "bool",
+        "Integer"          => "i32",
+        "Real"             => "f64",
+        "String"           => "String",
+        "UnlimitedNatural" => "u32",
+        x                  => x,
+    }.to_string()
+}
+
+/// Parses a duration like `500`, `500ms`, `5 s` or `2 min` into milliseconds,
+/// a bare number is taken as milliseconds
+pub fn parse_duration_ms(text: &str) -> Option<u32> {
+    let text  = text.trim();
+    let split = text.find(|c: char| !(c.is_digit(10) || c == '.')).unwrap_or(text.len());
+    let value = match text[..split].parse::<f64>() {
+        Ok(x)  => x,
+        Err(_) => return None
+    };
+    let factor = match text[split..].trim() {
+        "" | "ms"            => 1.0,
+        "s" | "sec"          => 1000.0,
+        "m" | "min"          => 60000.0,
+        "us"                 => 0.001,
+        "ns"                 => 0.000001,
+        _                    => return None
+    };
+    let ms = (value * factor).round();
+    if ms < 0.0 || ms > ::std::u32::MAX as f64 {
+        None
+    } else {
+        Some(ms as u32)
+    }
 }
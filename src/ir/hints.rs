@@ -0,0 +1,63 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use sxd_xpath::nodeset::Node;
+use ::xmi::XmiIndex;
+use ::error::Result;
+
+
+/// Generator hints the `HsmMachine` stereotype of the RustHsm profile gives a
+/// state machine
+#[derive(Debug, Clone)]
+pub struct MachineHints {
+    /// Traits the `Events` and `Timeout` enums derive besides `Debug` and `Clone`
+    pub derive : Vec<String>,
+    /// Whether the generated enums and function stubs are `pub`
+    pub public : bool,
+}
+
+impl Default for MachineHints {
+    fn default() -> Self {
+        MachineHints {
+            derive : Vec::new(),
+            public : true,
+        }
+    }
+}
+
+impl MachineHints {
+    pub fn from_xml(reader: &XmiIndex, node: Node) -> Result<Self> {
+        let mut hints = MachineHints::default();
+        if let Some(app) = reader.stereotype(node, "HsmMachine") {
+            for derive in try!(reader.tag_values(app, "derive")) {
+                hints.derive.extend(derive.split(',').map(|x| x.trim().to_string()).filter(|x| !x.is_empty()));
+            }
+            hints.public = match reader.get_attr(app, "visibility").as_ref().map(|x| x.as_str()) {
+                Some("public") | None => true,
+                Some("private")       => false,
+                Some(x)               => return Err(reader.error(app, format!("unknown visibility {:?}", x)))
+            };
+        }
+        Ok(hints)
+    }
+}
@@ -0,0 +1,86 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+/// A transition label of a text diagram, `event [guard] / effect`. Every part
+/// is optional and the effect is everything after the first `/` that is not
+/// inside brackets or parentheses.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Label {
+    pub trigger : Option<String>,
+    pub guard   : Option<String>,
+    pub effect  : Option<String>,
+}
+
+impl Label {
+    pub fn parse(text: &str) -> Self {
+        let text = text.trim();
+        let mut depth       = 0;
+        let mut guard_start = None;
+        let mut guard_end   = None;
+        let mut slash       = None;
+        for (i, c) in text.char_indices() {
+            match c {
+                '[' => {
+                    if depth == 0 && guard_start.is_none() {
+                        guard_start = Some(i);
+                    }
+                    depth += 1;
+                },
+                ']' => {
+                    if depth > 0 {
+                        depth -= 1;
+                    }
+                    if depth == 0 && guard_start.is_some() && guard_end.is_none() {
+                        guard_end = Some(i);
+                    }
+                },
+                '(' => depth += 1,
+                ')' if depth > 0 => depth -= 1,
+                '/' if depth == 0 => {
+                    slash = Some(i);
+                    break
+                },
+                _   => {}
+            }
+        }
+        let head_end = slash.unwrap_or(text.len());
+        let (trigger, guard) = match (guard_start, guard_end) {
+            (Some(s), Some(e)) => (&text[..s], Some(&text[s + 1..e])),
+            _                  => (&text[..head_end], None)
+        };
+        Label {
+            trigger : non_empty(trigger),
+            guard   : guard.and_then(non_empty),
+            effect  : slash.and_then(|x| non_empty(&text[x + 1..])),
+        }
+    }
+}
+
+fn non_empty(text: &str) -> Option<String> {
+    let text = text.trim();
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.to_string())
+    }
+}
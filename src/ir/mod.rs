@@ -22,15 +22,23 @@
  * SOFTWARE.
  */
 mod action;
+mod builder;
 mod condaction;
 mod event;
+mod hints;
+mod label;
+mod reader;
 mod state;
 mod subvertex;
 mod transition;
 
 pub use self::action::Action;
+pub use self::builder::{ModelBuilder, VertexKind, resolve_states, join_code};
 pub use self::condaction::CondAction;
-pub use self::event::Event;
+pub use self::event::{Event, SignalAttribute, parse_duration_ms};
+pub use self::hints::MachineHints;
+pub use self::label::Label;
+pub use self::reader::ModelReader;
 pub use self::state::State;
 pub use self::transition::Transition;
 pub use self::subvertex::Subvertex;
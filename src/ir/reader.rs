@@ -0,0 +1,50 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use std::collections::HashMap;
+
+use ::error::{Result, Diagnostic, Diagnostics};
+use super::State;
+
+
+/// A reader of a state machine model in some format.
+///
+/// Readers record recoverable model problems in their `Diagnostics`, the
+/// provided methods give access to them.
+pub trait ModelReader {
+    /// Reads the states of the state machine of the model
+    fn read_states(&self) -> Result<HashMap<String, State>>;
+
+    /// The diagnostics the reader records problems in
+    fn diagnostic_log(&self) -> &Diagnostics;
+
+    /// In collect-all mode recoverable model problems are recorded as diagnostics
+    /// and reading continues, otherwise the first one is returned as an error.
+    fn set_collect_all(&mut self, collect_all: bool) {
+        self.diagnostic_log().set_collect_all(collect_all);
+    }
+
+    fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.diagnostic_log().to_vec()
+    }
+}
@@ -23,17 +23,25 @@
  */
 use std::collections::HashMap;
 use sxd_xpath::nodeset::Node;
-use ::XmiReader;
+use ::xmi::XmiIndex;
+use ::error::{HsmGenError, Result};
+use ::location::SourceLocation;
 use super::{Transition, Subvertex, Event, CondAction, Action};
 
 
 #[derive(Debug, Clone)]
 pub struct State {
+    pub id          : String,
     pub name        : String,
     pub parent      : Option<String>,
     pub entry       : Option<String>,
     pub exit        : Option<String>,
     pub actions     : HashMap<Event, Vec<CondAction>>,
+    pub location    : Option<SourceLocation>,
+    /// Predicate of the `#[cfg]` gating the state impl, from the `Cfg` stereotype
+    pub cfg         : Option<String>,
+    /// The comments of the state in the model
+    pub doc         : Option<String>,
 
     // used only in xmi reading
     pub transitions       : Vec<Transition>,
@@ -41,18 +49,26 @@ pub struct State {
 }
 
 impl State {
-    pub fn from_xml(reader: &XmiReader, node: Node) -> Self {
-        let id = reader.get_attr(node, "id").unwrap();
+    pub fn new(id: &str, name: &str) -> Self {
+        State {
+            id          : id.to_string(),
+            name        : name.to_string(),
+            parent      : None,
+            entry       : None,
+            exit        : None,
+            actions     : HashMap::new(),
+            location    : None,
+            cfg         : None,
+            doc         : None,
+            transitions : Vec::new(),
+            initial_transition: None,
+        }
+    }
+
+    pub fn from_xml(reader: &XmiIndex, node: Node) -> Result<Self> {
+        let id = try!(reader.req_attr(node, "id"));
         let mut hm = HashMap::new();
-        if let Some(mut do_activ) = get_node_opt!(reader, node, "doActivity/body").map(|x| x.string_value()) {
-            let pat = [ ( "&gt;"  , ">" ),
-                        ( "&lt;"  , "<" ),
-                        ( "&amp;" , "&" ),
-                        ( "&#39;" , "\\"),
-                        ( "&quot;", "\"") ];
-            for a in &pat {
-                do_activ = do_activ.replace(a.0, a.1);
-            }
+        if let Some(do_activ) = try!(reader.behavior(node, "doActivity")) {
             for group in do_activ.split("\n") {
                 let evt_activ: Vec<&str> = group.split("=>").collect();
                 if evt_activ.len() == 2 {
@@ -60,61 +76,89 @@ impl State {
                         match evt_activ[0].trim() {
                             "_"   => Event::UserAny,
                             "___" => Event::Any,
-                            x     => Event::Signal {
-                                id  : "DUMMY".to_string(),
-                                name: x.to_string()
+                            // The same event as the transitions triggered by the signal
+                            x     => match reader.signal_event(x) {
+                                Some(evt_node) => try!(Event::from_xml(reader, evt_node)),
+                                None           => Event::Signal {
+                                    id        : x.to_string(),
+                                    name      : x.to_string(),
+                                    attributes: Vec::new(),
+                                    doc       : None,
+                                    location  : reader.location(node),
+                                }
                             }
                         },
                         vec!(CondAction {
+                            doc     : None,
                             guard   : None,
                             effect  : Some(evt_activ[1].trim().to_string()),
                             action  : Action::Ignore,
                         })
                     );
                 } else {
-                    panic!("Could not split do activity into evt and activity: {:?}", evt_activ)
+                    return Err(reader.error(node, format!("Could not split do activity into evt and activity: {:?}", evt_activ)))
                 }
             }
         }
-        State {
-            name        : reader.get_attr(node, "name").expect("State without name"),
-            parent      : reader.parent_state_node(node).map(|x| reader.get_attr(x, "name").expect("State parent without name")),
-            entry       : get_node_opt!(reader, node, "entry/body").map(|x| x.string_value()),
-            exit        : get_node_opt!(reader, node, "exit/body").map(|x| x.string_value()),
-            actions     : hm,
-            transitions : get_ns!(reader, &format!("//transition[@source='{}']", id)).iter()
-                                    .map(|x| Transition::from_xml(reader, x)).collect(),
-            initial_transition: {
-                let opts = get_ns!(reader, node, "region/subvertex").iter().filter(|x| {
-                    reader.get_attr(x.clone(), "type").unwrap().as_str() == "uml:Pseudostate"
-                    && reader.get_attr(x.clone(), "kind").is_none()
-                }).collect::<Vec<Node>>();
-                match opts.len() {
-                    0 => None,
-                    1 => Some(Transition::from_xml(
-                        reader,
-                        get_node!(reader, &format!(
-                            "//transition[@source='{}']",
-                            reader.get_attr(opts[0], "id").unwrap()
-                    )))),
-                    _ => panic!("State {} initial state options", opts.len())
-                }
+        let parent = match reader.parent_state_node(node) {
+            Some(p_node) => Some(try!(reader.name_attr(p_node))),
+            None         => None
+        };
+        let mut transitions = Vec::new();
+        for trans_node in try!(reader.transitions_from(&id)) {
+            match Transition::from_xml(reader, trans_node) {
+                Ok(t)  => transitions.push(t),
+                Err(e) => try!(reader.recover(e)),
+            }
+        }
+        let mut opts = Vec::new();
+        for x in get_ns!(reader, node, "region/subvertex").iter() {
+            if reader.is_initial(x) {
+                opts.push(x);
             }
         }
+        let initial_transition = match opts.len() {
+            0 => None,
+            1 => {
+                let init_trans = try!(reader.transitions_from(&try!(reader.req_attr(opts[0], "id"))));
+                match init_trans.len() {
+                    1 => Some(try!(Transition::from_xml(reader, init_trans[0]))),
+                    n => return Err(reader.error(opts[0], format!("Initial state has {} transitions, expected 1", n)))
+                }
+            },
+            _ => return Err(reader.error(node, format!("State has {} initial state options", opts.len())))
+        };
+        Ok(State {
+            id          : id,
+            name        : try!(reader.name_attr(node)),
+            parent      : parent,
+            entry       : try!(reader.behavior(node, "entry")),
+            exit        : try!(reader.behavior(node, "exit")),
+            actions     : hm,
+            location    : reader.location(node),
+            cfg         : reader.stereotype(node, "Cfg")
+                                .and_then(|app| reader.get_attr(app, "feature"))
+                                .map(|x| format!("feature = {:?}", x)),
+            doc         : try!(reader.comments(node)),
+            transitions : transitions,
+            initial_transition: initial_transition,
+        })
     }
 
-    pub fn add_action(&mut self, mut t: Transition, sm: &HashMap<String, State>, vm: &HashMap<String, Subvertex>) {
+    pub fn add_action(&mut self, mut t: Transition, sm: &HashMap<String, State>, vm: &HashMap<String, Subvertex>) -> Result<()> {
         match t.trigger.take() {
             Some(evt) => {
-                let ca = CondAction::from_transition(t, sm, vm);
+                let ca = try!(CondAction::from_transition(t, sm, vm));
                 if let Some(cond_act_vec) = self.actions.get_mut(&evt) {
                     cond_act_vec.push(ca);
-                    return
+                    return Ok(())
                 }
                 self.actions.insert(evt, vec!(ca));
-                return
+                Ok(())
             },
-            None => panic!("ActionMap add_transition without trigger {:?}", t)
+            None => Err(HsmGenError::model("Transition from a state without a trigger")
+                            .context("uml:Transition", t.id)
+                            .with_location(t.location))
         }
     }
 }
@@ -22,7 +22,8 @@
  * SOFTWARE.
  */
 use sxd_xpath::nodeset::Node;
-use ::XmiReader;
+use ::xmi::XmiIndex;
+use ::error::Result;
 use super::{State, Transition};
 
 
@@ -36,38 +37,44 @@ pub enum Subvertex {
 }
 
 impl Subvertex {
-    pub fn from_xml(reader: &XmiReader, node: Node) -> Option<Self> {
-        let id = reader.get_attr(node, "id").unwrap();
-        match reader.get_attr(node, "type").unwrap().as_str() {
-            "uml:State"       => Some(Subvertex::State {id: id, state: State::from_xml(reader, node)}),
-            "uml:FinalState"  => Some(Subvertex::Final {id: id}),
+    pub fn from_xml(reader: &XmiIndex, node: Node) -> Result<Option<Self>> {
+        let id = try!(reader.req_attr(node, "id"));
+        match try!(reader.req_type(node)).as_str() {
+            "uml:State"       => Ok(Some(Subvertex::State {id: id, state: try!(State::from_xml(reader, node))})),
+            "uml:FinalState"  => Ok(Some(Subvertex::Final {id: id})),
+            "uml:Pseudostate" if reader.is_initial(node) => {
+                match reader.parent_state_node(node) {
+                    Some(p_node) => { try!(reader.req_attr(p_node, "id")); Ok(None) },
+                    None         => Ok(Some(Subvertex::Initial {id: id}))
+                }
+            },
             "uml:Pseudostate" => {
-                if let Some(kind) = reader.get_attr(node, "kind") {
-                    match kind.as_str() {
-                        "junction" => Some(Subvertex::Junction {
+                let kind = try!(reader.req_attr(node, "kind"));
+                match kind.as_str() {
+                    "junction" => {
+                        let trans_nodes = try!(reader.transitions_from(&id));
+                        if trans_nodes.len() != 1 {
+                            return Err(reader.error(node, format!("Junction has {} outgoing transitions, expected 1", trans_nodes.len())))
+                        }
+                        Ok(Some(Subvertex::Junction {
                             id:         id.clone(),
-                            transition: Transition::from_xml(
-                                reader,
-                                get_node!(reader, &format!("//transition[@source='{}']", id))
-                            )
-                        }),
-                        "choice"   => {
-                            let mut transitions = Vec::new();
-                            for trans_node in get_ns!(reader, &format!("//transition[@source='{}']", id)) {
-                                transitions.push(Transition::from_xml(reader, trans_node));
+                            transition: try!(Transition::from_xml(reader, trans_nodes[0]))
+                        }))
+                    },
+                    "choice"   => {
+                        let mut transitions = Vec::new();
+                        for trans_node in try!(reader.transitions_from(&id)) {
+                            match Transition::from_xml(reader, trans_node) {
+                                Ok(t)  => transitions.push(t),
+                                Err(e) => try!(reader.recover(e)),
                             }
-                            Some(Subvertex::Choice {id: id, transitions: transitions})
-                        },
-                        _ => panic!("Pseudostate with unknown type")
-                    }
-                } else {
-                    match reader.parent_state_node(node).map(|x| reader.get_attr(x, "id").expect("Subvertex parent state without id")) {
-                        Some(p_id) => None,
-                        None       => Some(Subvertex::Initial {id: id})
-                    }
+                        }
+                        Ok(Some(Subvertex::Choice {id: id, transitions: transitions}))
+                    },
+                    _ => Err(reader.error(node, format!("Pseudostate with unknown kind {:?}", kind)))
                 }
             },
-            _ => panic!("subvertex with unknown type")
+            _ => Err(reader.error(node, "subvertex with unknown type"))
         }
     }
 }
@@ -22,36 +22,86 @@
  * SOFTWARE.
  */
 use sxd_xpath::nodeset::Node;
-use ::XmiReader;
+use ::xmi::XmiIndex;
+use ::error::Result;
+use ::location::SourceLocation;
 use super::Event;
 
 
 #[derive(Debug, Clone)]
 pub struct Transition {
+    pub id:        String,
     pub source_id: String,
     pub target_id: String,
     pub guard:     Option<String>,
     pub effect:    Option<String>,
     pub trigger:   Option<Event>,
+    pub doc:       Option<String>,
+    pub location:  Option<SourceLocation>,
 }
 
 impl Transition {
-    pub fn from_xml(reader: &XmiReader, node: Node) -> Self {
+    pub fn new(id: &str, source_id: &str, target_id: &str) -> Self {
         Transition {
-            source_id: reader.get_attr(node, "source").expect("Transition without source"),
-            target_id: reader.get_attr(node, "target").expect("Transition without target"),
-            guard:     get_node_opt!(reader, node, "ownedRule/specification").map(|x|
-                match reader.get_attr(x, "type").unwrap().as_str() {
-                    "uml:OpaqueExpression" => get_node!(reader, x, "body").string_value(),
-                    "uml:LiteralString"    => reader.get_attr(x, "value").expect("Transition guard without value"),
-                    _ => panic!("Transition guard specification type unknown")
-                }
-            ),
-            effect:    get_node_opt!(reader, node, "effect/body").map(|x| x.string_value()),
-            trigger:   get_node_opt!(reader, node, "trigger").map(|trig_node| Event::from_xml(reader, {
-                let evt_id = reader.get_attr(trig_node, "event").expect("Transition trigger without event");
-                get_ns!(reader, "//packagedElement").iter().filter(|x| reader.get_attr(x.clone(), "id").unwrap() == evt_id).next().unwrap()
-            })),
+            id:        id.to_string(),
+            source_id: source_id.to_string(),
+            target_id: target_id.to_string(),
+            guard:     None,
+            effect:    None,
+            trigger:   None,
+            doc:       None,
+            location:  None,
         }
     }
+
+    pub fn from_xml(reader: &XmiIndex, node: Node) -> Result<Self> {
+        // A transition may own several rules, the guard attribute tells which one is the guard
+        let guard_spec = match try!(reader.get_ref(node, "guard")) {
+            Some(guard_id) => {
+                let mut spec = None;
+                for rule in get_ns!(reader, node, "ownedRule").iter() {
+                    if reader.get_attr(rule, "id").as_ref() == Some(&guard_id) {
+                        spec = get_node_opt!(reader, rule, "specification");
+                    }
+                }
+                spec
+            },
+            None           => match get_node_opt!(reader, node, "ownedRule/specification") {
+                Some(spec) => Some(spec),
+                // Enterprise Architect
+                None       => get_node_opt!(reader, node, "guard/specification")
+            }
+        };
+        let guard = match guard_spec {
+            Some(spec) => match try!(reader.req_type(spec)).as_str() {
+                "uml:OpaqueExpression" => match try!(reader.body(spec)) {
+                    Some(body) => Some(body),
+                    None       => return Err(reader.error(spec, "Transition guard without body"))
+                },
+                "uml:LiteralString"    => Some(try!(reader.req_attr(spec, "value"))),
+                _ => {
+                    try!(reader.recover(reader.error(spec, "Transition guard specification type unknown")));
+                    None
+                }
+            },
+            None       => None
+        };
+        let trigger = match get_node_opt!(reader, node, "trigger") {
+            Some(trig_node) => match try!(reader.trigger_event(trig_node)) {
+                Some(evt_node) => Some(try!(Event::from_xml(reader, evt_node))),
+                None           => return Err(reader.error(trig_node, "Transition trigger without event"))
+            },
+            None            => None
+        };
+        Ok(Transition {
+            id:        try!(reader.req_attr(node, "id")),
+            source_id: try!(reader.req_ref(node, "source")),
+            target_id: try!(reader.req_ref(node, "target")),
+            guard:     guard,
+            effect:    try!(reader.behavior(node, "effect")),
+            trigger:   trigger,
+            doc:       try!(reader.comments(node)),
+            location:  reader.location(node),
+        })
+    }
 }
@@ -21,31 +21,19 @@
  * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
  * SOFTWARE.
  */
-use std::mem;
+use rustc_serialize::json::{Json, ParserError};
 
-use sxd_document::Package;
-use sxd_document::dom::Document;
+use ::error::{HsmGenError, Result};
+use ::location::SourceLocation;
 
 
-pub struct InnerXmiReader <'d> {
-    package : Package,
-    doc     : Document<'d>,
-}
-
-impl<'d> InnerXmiReader<'d> {
-    pub fn new(pkg: Package) -> Self {
-        InnerXmiReader {
-            package : pkg,
-            doc     : unsafe {mem::uninitialized()},
-        }
-    }
-    pub fn init(&self) {
-        unsafe {
-            let mut dest: *mut Document = mem::transmute(&self.doc);
-            *dest = self.package.as_document();
-        }
-    }
-    pub fn doc(&self) -> &Document<'d> {
-        &self.doc
-    }
+/// Parses the JSON document `src` read from `file`
+pub fn parse_json(file: &str, src: &str) -> Result<Json> {
+    Json::from_str(src).map_err(|e| {
+        let location = match e {
+            ParserError::SyntaxError(_, line, col) => Some(SourceLocation::from_line_col(file, src, line, col)),
+            ParserError::IoError(..)               => None,
+        };
+        HsmGenError::parse(format!("could not parse {}: {}", file, e)).with_location(location)
+    })
 }
@@ -21,24 +21,48 @@
  * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
  * SOFTWARE.
  */
-#![feature(quote, rustc_private)]
-#![feature(box_syntax)]
-#![feature(convert)]
-#![allow(unused_variables, dead_code)]
+#![allow(unused_variables, dead_code, unused_macros)]
 
 
 #[macro_use]
 extern crate log;
-extern crate syntax;
-extern crate rustc;
-extern crate rustc_driver;
 extern crate sxd_document;
 extern crate sxd_xpath;
+extern crate rustc_serialize;
+extern crate flate2;
+extern crate toml;
+extern crate yaml_rust;
 
+mod error;
+mod location;
+mod xml;
+mod json;
+mod diagram;
 #[macro_use]
 mod xmi;
+mod mdj;
+mod scxml;
+mod plantuml;
+mod mermaid;
+mod qm;
+mod sct;
+mod xstate;
+mod drawio;
+mod text;
 mod generate;
 mod ir;
 
+pub use error::{HsmGenError, ErrorKind, Severity, Diagnostic, Diagnostics};
+pub use location::SourceLocation;
 pub use generate::HsmGenerator;
-pub use xmi::XmiReader;
+pub use ir::{State, Event, SignalAttribute, CondAction, Action, MachineHints, ModelReader};
+pub use xmi::{XmiReader, XmiModel, Dialect};
+pub use mdj::MdjReader;
+pub use scxml::ScxmlReader;
+pub use plantuml::PlantUmlReader;
+pub use mermaid::MermaidReader;
+pub use qm::QmReader;
+pub use sct::SctReader;
+pub use xstate::XStateReader;
+pub use drawio::DrawioReader;
+pub use text::TextReader;
@@ -0,0 +1,248 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use std::fmt;
+use std::io::prelude::*;
+use std::fs::File;
+use std::collections::HashMap;
+
+use ::error::{HsmGenError, Result};
+
+
+/// Position of a model element in its source file. Line and column start at 1.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct SourceLocation {
+    pub file   : String,
+    pub byte   : usize,
+    pub line   : usize,
+    pub column : usize,
+}
+
+impl SourceLocation {
+    pub fn from_byte(file: &str, src: &str, byte: usize) -> Self {
+        let line_start = src[..byte].rfind('\n').map(|x| x + 1).unwrap_or(0);
+        SourceLocation {
+            file   : file.to_string(),
+            byte   : byte,
+            line   : src[..byte].matches('\n').count() + 1,
+            column : src[line_start..byte].chars().count() + 1,
+        }
+    }
+
+    /// Location of `line` and `column` (both starting at 1) as reported by a parser
+    pub fn from_line_col(file: &str, src: &str, line: usize, column: usize) -> Self {
+        let line_start = src.split('\n').take(line.saturating_sub(1)).fold(0, |acc, x| acc + x.len() + 1);
+        let line_start = ::std::cmp::min(line_start, src.len());
+        let byte = src[line_start..].char_indices().nth(column.saturating_sub(1))
+                                    .map(|(i, _)| line_start + i)
+                                    .unwrap_or(src.len());
+        SourceLocation {
+            file   : file.to_string(),
+            byte   : byte,
+            line   : line,
+            column : column,
+        }
+    }
+}
+
+/// Reads a whole model file
+pub fn read_source(file: &str) -> Result<String> {
+    let mut f = try!(File::open(file).map_err(|e|
+        HsmGenError::io(format!("could not open {}: {}", file, e))
+    ));
+    let mut s = String::new();
+    try!(f.read_to_string(&mut s).map_err(|e|
+        HsmGenError::io(format!("could not read {}: {}", file, e))
+    ));
+    Ok(s)
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// Scans the start tags of an XML document and records where every element
+/// with an `id` attribute (in any namespace, so `xmi:id` too) begins.
+pub fn index_xml_ids(file: &str, src: &str) -> HashMap<String, SourceLocation> {
+    index_xml_attr(file, src, "id")
+}
+
+/// Like `index_xml_ids` for the values of attribute `attr`, the first element
+/// with a value wins.
+pub fn index_xml_attr(file: &str, src: &str, attr: &str) -> HashMap<String, SourceLocation> {
+    let mut hm    = HashMap::new();
+    let mut line  = 1;
+    let mut line_start = 0;
+    let mut pos   = 0;
+    let bytes     = src.as_bytes();
+    while let Some(off) = src[pos..].find('<') {
+        let start = pos + off;
+        for (i, b) in bytes[pos..start].iter().enumerate() {
+            if *b == b'\n' {
+                line += 1;
+                line_start = pos + i + 1;
+            }
+        }
+        let rest = &src[start..];
+        let skip_to = |end: &str| rest.find(end).map(|x| start + x + end.len()).unwrap_or(src.len());
+        let end = if rest.starts_with("<!--") {
+            skip_to("-->")
+        } else if rest.starts_with("<![CDATA[") {
+            skip_to("]]>")
+        } else if rest.starts_with("<?") {
+            skip_to("?>")
+        } else if rest.starts_with("</") || rest.starts_with("<!") {
+            skip_to(">")
+        } else {
+            let tag_end = tag_end(rest).map(|x| start + x + 1).unwrap_or(src.len());
+            if let Some(id) = attr_value(&src[start..tag_end], attr) {
+                hm.entry(id).or_insert(SourceLocation {
+                    file   : file.to_string(),
+                    byte   : start,
+                    line   : line,
+                    column : src[line_start..start].chars().count() + 1,
+                });
+            }
+            tag_end
+        };
+        for (i, b) in bytes[start..end].iter().enumerate() {
+            if *b == b'\n' {
+                line += 1;
+                line_start = start + i + 1;
+            }
+        }
+        pos = end;
+    }
+    hm
+}
+
+/// Offset of the `>` closing the start tag at the beginning of `tag`
+fn tag_end(tag: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in tag.char_indices() {
+        match (quote, c) {
+            (None,    '"') | (None, '\'') => quote = Some(c),
+            (Some(q), _  ) if q == c      => quote = None,
+            (None,    '>')                => return Some(i),
+            _                             => {}
+        }
+    }
+    None
+}
+
+fn attr_value(tag: &str, attr: &str) -> Option<String> {
+    let mut rest = tag;
+    while let Some(eq) = rest.find('=') {
+        let name = rest[..eq].trim_end().rsplit(|c: char| c.is_whitespace()).next().unwrap_or("");
+        let after = rest[eq + 1..].trim_start();
+        let quote = match after.chars().next() {
+            Some(q) if q == '"' || q == '\'' => q,
+            _                                => return None
+        };
+        let value_end = match after[1..].find(quote) {
+            Some(x) => x + 1,
+            None    => return None
+        };
+        if name == attr || (name.ends_with(attr) && name[..name.len() - attr.len()].ends_with(':')) {
+            return Some(after[1..value_end].to_string())
+        }
+        rest = &after[value_end + 1..];
+    }
+    None
+}
+
+/// Records where every `"_id": "..."` member of a JSON document is, the
+/// location of an object is that of its id.
+pub fn index_json_ids(file: &str, src: &str) -> HashMap<String, SourceLocation> {
+    let mut hm  = HashMap::new();
+    let mut pos = 0;
+    while let Some(off) = src[pos..].find("\"_id\"") {
+        let start = pos + off;
+        pos = start + 5;
+        let after = src[pos..].trim_start();
+        if !after.starts_with(':') {
+            continue
+        }
+        let value = after[1..].trim_start();
+        if !value.starts_with('"') {
+            continue
+        }
+        if let Some(end) = value[1..].find('"') {
+            hm.insert(value[1..end + 1].to_string(), SourceLocation::from_byte(file, src, start));
+        }
+    }
+    hm
+}
+
+/// Records where every member of a JSON document is, by its JSON pointer
+/// like `/states/closed/on/OPEN`. The location of a member is that of its key.
+pub fn index_json_keys(file: &str, src: &str) -> HashMap<String, SourceLocation> {
+    // The key of the member of each enclosing object, the index in each array
+    enum Frame { Object(Option<String>), Array(usize) }
+    let pointer = |stack: &[Frame]| json_pointer(&stack.iter().filter_map(|frame| match *frame {
+        Frame::Object(ref key) => key.clone(),
+        Frame::Array(i)        => Some(i.to_string()),
+    }).collect::<Vec<_>>());
+    let mut hm = HashMap::new();
+    let mut stack = Vec::new();
+    let mut expect_key = false;
+    let mut chars = src.char_indices();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '{' => { stack.push(Frame::Object(None)); expect_key = true },
+            '[' => { stack.push(Frame::Array(0)); expect_key = false },
+            '}' | ']' => { stack.pop(); },
+            ':' => expect_key = false,
+            ',' => match stack.last_mut() {
+                Some(&mut Frame::Object(ref mut key)) => { *key = None; expect_key = true },
+                Some(&mut Frame::Array(ref mut i))    => *i += 1,
+                None                                  => {}
+            },
+            '"' => {
+                let mut value = String::new();
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '"'  => break,
+                        '\\' => if let Some((_, c)) = chars.next() { value.push(c) },
+                        c    => value.push(c),
+                    }
+                }
+                if expect_key {
+                    if let Some(&mut Frame::Object(ref mut key)) = stack.last_mut() {
+                        *key = Some(value);
+                    }
+                    hm.insert(pointer(&stack), SourceLocation::from_byte(file, src, start));
+                }
+            },
+            _   => {}
+        }
+    }
+    hm
+}
+
+/// The JSON pointer of the member reached through `keys`
+pub fn json_pointer<S: AsRef<str>>(keys: &[S]) -> String {
+    keys.iter().map(|key| format!("/{}", key.as_ref().replace('~', "~0").replace('/', "~1"))).collect()
+}
@@ -32,60 +32,228 @@ extern crate hsm_gen;
 extern crate clap;
 extern crate sxd_xpath;
 
+use std::fs;
+use std::io;
+use std::process;
+use std::path::Path;
+use std::collections::HashMap;
 use clap::{App, Arg};
+use hsm_gen::{HsmGenError, Severity, Diagnostic, State, MachineHints, ModelReader, XmiReader, MdjReader, ScxmlReader, PlantUmlReader, MermaidReader, QmReader, SctReader,
+              XStateReader, DrawioReader, TextReader};
 
 
+struct Options {
+    input       : String,
+    output      : String,
+    func_stubs  : Option<String>,
+    keep_going  : bool,
+    machine     : Option<String>,
+    all         : bool,
+    list        : bool,
+    search_path : Vec<String>,
+}
+
 fn main() {
     conf_logger();
-    let (inp, outp, fstub) = get_options();
-    let xmireader = hsm_gen::XmiReader::from_file(&inp);
-    // xmireader.print(&outp);
-    let states = xmireader.read_states();
+    let opts = get_options();
+    if let Err(e) = generate(&opts) {
+        error!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn generate(opts: &Options) -> Result<(), HsmGenError> {
+    let ext = Path::new(&opts.input).extension().and_then(|x| x.to_str()).unwrap_or("").to_lowercase();
+    let input = opts.input.as_str();
+    let mut reader: Box<ModelReader> = match ext.as_str() {
+        "mdj"                      => Box::new(try!(MdjReader::from_file(input))),
+        "scxml"                    => Box::new(try!(ScxmlReader::from_file(input))),
+        "puml" | "plantuml" | "pu" => Box::new(try!(PlantUmlReader::from_file(input))),
+        "mmd" | "mermaid"          => Box::new(try!(MermaidReader::from_file(input))),
+        "md" | "markdown"          => Box::new(try!(MermaidReader::from_markdown(input))),
+        "qm"                       => Box::new(try!(QmReader::from_file(input))),
+        "sct"                      => Box::new(try!(SctReader::from_file(input))),
+        "json"                     => Box::new(try!(XStateReader::from_file(input))),
+        "drawio" | "dio"           => Box::new(try!(DrawioReader::from_file(input))),
+        "toml" | "yaml" | "yml"    => Box::new(try!(TextReader::from_file(input))),
+        _                          => return generate_xmi(opts),
+    };
+    if opts.machine.is_some() || opts.all || opts.list {
+        return Err(HsmGenError::model(format!("{} holds one state machine, only XMI models can hold several", opts.input)))
+    }
+    reader.set_collect_all(opts.keep_going);
+    let states = reader.read_states();
+    try!(check_diagnostics(&reader.diagnostics(), &opts.input));
+    write_hsm(&try!(states), MachineHints::default(), &opts.output)
+}
 
+/// XMI models may hold several state machines, one of them is picked by name
+/// or each is written to its own module in the output directory
+fn generate_xmi(opts: &Options) -> Result<(), HsmGenError> {
+    let mut reader = if opts.input == "-" {
+        try!(XmiReader::from_reader(io::stdin()))
+    } else {
+        try!(XmiReader::from_file(&opts.input))
+    };
+    reader.set_collect_all(opts.keep_going);
+    for dir in opts.search_path.iter() {
+        try!(reader.add_search_path(dir));
+    }
+    let model = try!(reader.model());
+    if opts.list {
+        for machine in try!(model.state_machines()) {
+            println!("{}", machine);
+        }
+        return Ok(())
+    }
+    if !opts.all {
+        let states = match opts.machine {
+            Some(ref machine) => model.read_machine(machine),
+            None              => model.read_states(),
+        };
+        try!(check_diagnostics(&model.diagnostics(), &opts.input));
+        let hints = try!(model.machine_hints(opts.machine.as_ref().map(|x| x.as_str())));
+        return write_hsm(&try!(states), hints, &opts.output)
+    }
+    let machines = try!(model.state_machines());
+    // A model without state machine elements has nothing to put in modules
+    if machines.is_empty() {
+        return Err(HsmGenError::model(format!("{} has no state machines to write with --all", opts.input)))
+    }
+    let mut modules = Vec::new();
+    for machine in machines.iter() {
+        let states = model.read_machine(machine);
+        let hints = try!(model.machine_hints(Some(machine)));
+        modules.push((module_name(machine, &machines), states, hints));
+    }
+    try!(check_diagnostics(&model.diagnostics(), &opts.input));
+    try!(fs::create_dir_all(&opts.output).map_err(|e|
+        HsmGenError::io(format!("could not create directory {}: {}", opts.output, e))
+    ));
+    for (module, states, hints) in modules {
+        let file = Path::new(&opts.output).join(format!("{}.rs", module));
+        info!("Writing state machine {} to {}", module, file.display());
+        try!(write_hsm(&try!(states), hints, &file.to_string_lossy()));
+    }
+    Ok(())
+}
+
+/// Module name of the state machine `machine`, from its own name unless
+/// another machine in `machines` has the same one
+fn module_name(machine: &str, machines: &[String]) -> String {
+    let own = |x: &str| x.rsplit("::").next().unwrap_or("").to_string();
+    let unique = machines.iter().filter(|x| own(x) == own(machine)).count() == 1;
+    let name = if unique { own(machine) } else { machine.replace("::", "_") };
+    let mut module = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 && !module.ends_with('_') {
+                module.push('_');
+            }
+            module.extend(c.to_lowercase());
+        } else if c.is_alphanumeric() {
+            module.push(c);
+        } else if !module.ends_with('_') {
+            module.push('_');
+        }
+    }
+    module
+}
+
+/// Logs the diagnostics of reading `input`, any errors among them fail generation
+fn check_diagnostics(diagnostics: &[Diagnostic], input: &str) -> Result<(), HsmGenError> {
+    for diag in diagnostics.iter() {
+        match diag.severity {
+            Severity::Warning => warn!("{}", diag),
+            Severity::Error   => error!("{}", diag),
+        }
+    }
+    let errors = diagnostics.iter().filter(|x| x.severity == Severity::Error).count();
+    if errors > 0 {
+        return Err(HsmGenError::model(format!("{} problems found in {}", errors, input)))
+    }
+    Ok(())
+}
+
+fn write_hsm(states: &HashMap<String, State>, hints: MachineHints, output: &str) -> Result<(), HsmGenError> {
     let mut generator = hsm_gen::HsmGenerator::new(true);
-    generator.create_event_enum(&states);
-    generator.create_hsm_objects(&states);
-    generator.create_state_parent_impls(&states);
-    generator.create_state_impls(&states);
-    generator.print(&outp);
-    // if let Some(fstubfle) = fstub {
+    generator.set_hints(hints);
+    try!(generator.create_event_enum(states));
+    try!(generator.create_hsm_objects(states));
+    try!(generator.create_state_parent_impls(states));
+    try!(generator.create_state_impls(states));
+    try!(generator.print(output));
+    // if let Some(ref fstubfle) = opts.func_stubs {
     //     let mut gen2 = hsm_gen::HsmGenerator::new(false);
-    //     gen2.create_function_stubs(&state_impls);
-    //     gen2.print(&fstubfle);
+    //     try!(gen2.create_function_stubs(&state_impls));
+    //     try!(gen2.print(fstubfle));
     // }
+    Ok(())
 }
 
-fn get_options() -> (String, String, Option<String>) {
+fn get_options() -> Options {
     let matches = App::new("HSM Generator")
                   .version("0.1.0")
                   .author("Mattis Marjak <mattis.marjak@gmail.com>")
-                  .about("Generates HSM source from xmi files")
+                  .about("Generates HSM source from UML, SCXML, statechart tool and diagram files")
                   .arg(Arg::with_name("INPUT")
                        .short("i")
-                       .help("Sets the input file to use")
+                       .help("Sets the input file to use, - reads an XMI model from stdin")
                        .required(true)
                        .takes_value(true))
                   .arg(Arg::with_name("OUTPUT")
                        .short("o")
                        .help("Saves output to this file")
-                       .required(true)
+                       .required_unless("LIST")
                        .takes_value(true))
                       .arg(Arg::with_name("FUNC_STUBS")
                        .short("f")
                        .help("Writes function stubs to this file")
                        .required(false)
                        .takes_value(true))
+                  .arg(Arg::with_name("KEEP_GOING")
+                       .short("k")
+                       .long("keep-going")
+                       .help("Reports every problem in the model instead of stopping at the first one"))
+                  .arg(Arg::with_name("MACHINE")
+                       .short("m")
+                       .long("machine")
+                       .help("Generates the state machine with this (qualified) name of an XMI model")
+                       .takes_value(true))
+                  .arg(Arg::with_name("ALL")
+                       .short("a")
+                       .long("all")
+                       .help("Generates every state machine of an XMI model, OUTPUT is a directory with a file per machine")
+                       .conflicts_with("MACHINE"))
+                  .arg(Arg::with_name("LIST")
+                       .short("l")
+                       .long("list")
+                       .help("Lists the state machines of an XMI model"))
+                  .arg(Arg::with_name("SEARCH_PATH")
+                       .short("I")
+                       .long("search-path")
+                       .help("Looks for documents an XMI model refers to in this directory too")
+                       .takes_value(true)
+                       .multiple(true)
+                       .number_of_values(1))
                   .get_matches();
-    (matches.value_of("INPUT").unwrap().to_string(),
-     matches.value_of("OUTPUT").unwrap().to_string(),
-     matches.value_of("FUNC_STUBS").map(|x| x.to_string()))
+    Options {
+        input       : matches.value_of("INPUT").unwrap().to_string(),
+        output      : matches.value_of("OUTPUT").unwrap_or("").to_string(),
+        func_stubs  : matches.value_of("FUNC_STUBS").map(|x| x.to_string()),
+        keep_going  : matches.is_present("KEEP_GOING"),
+        machine     : matches.value_of("MACHINE").map(|x| x.to_string()),
+        all         : matches.is_present("ALL"),
+        list        : matches.is_present("LIST"),
+        search_path : matches.values_of("SEARCH_PATH").map(|x| x.map(|x| x.to_string()).collect()).unwrap_or(Vec::new()),
+    }
 }
 
 fn conf_logger() {
     let logger_config = fern::DispatchConfig {
         format: Box::new(|msg: &str, level: &log::LogLevel, _location: &log::LogLocation| {
             let t = time::now();
-            let ms = t.tm_nsec/1000_000;
+            let ms = t.tm_nsec/1_000_000;
             format!("{}.{:3} [{}] {}", t.strftime("%Y-%m-%dT%H:%M:%S").unwrap(), ms, level, msg)
         }),
         output: vec![fern::OutputConfig::stderr()],
@@ -0,0 +1,263 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use std::collections::HashMap;
+
+use rustc_serialize::json::Json;
+
+use ::error::{HsmGenError, Result, Diagnostics};
+use ::location::{SourceLocation, index_json_ids, read_source};
+use ::json::parse_json;
+use ::ir::{State, Transition, Event, ModelBuilder, VertexKind, parse_duration_ms, join_code, ModelReader};
+
+
+/// Reads state machines from StarUML `.mdj` project files.
+///
+/// StarUML saves the whole project as one JSON document, elements reference
+/// each other with `{"$ref": "<_id>"}` objects. The first `UMLStateMachine`
+/// found in the project is read.
+pub struct MdjReader {
+    file        : String,
+    json        : Json,
+    diagnostics : Diagnostics,
+    locations   : HashMap<String, SourceLocation>,
+}
+
+impl MdjReader {
+    pub fn from_file(file: &str) -> Result<Self> {
+        let s = try!(read_source(file));
+        let json = try!(parse_json(file, &s));
+        Ok(MdjReader {
+            file        : file.to_string(),
+            json        : json,
+            diagnostics : Diagnostics::new(),
+            locations   : index_json_ids(file, &s),
+        })
+    }
+
+    fn read_region(&self, builder: &mut ModelBuilder, index: &HashMap<String, &Json>, region: &Json, parent: Option<&str>) -> Result<()> {
+        for vertex in array(region, "vertices") {
+            if let Err(e) = self.read_vertex(builder, index, vertex, parent) {
+                try!(self.diagnostics.recover(e));
+            }
+        }
+        for trans in array(region, "transitions") {
+            if let Err(e) = self.read_transition(builder, index, trans) {
+                try!(self.diagnostics.recover(e));
+            }
+        }
+        Ok(())
+    }
+
+    fn read_vertex(&self, builder: &mut ModelBuilder, index: &HashMap<String, &Json>, vertex: &Json, parent: Option<&str>) -> Result<()> {
+        let id = try!(self.req_str(vertex, "_id"));
+        let location = self.locations.get(id).cloned();
+        match string(vertex, "_type").unwrap_or("") {
+            "UMLState" => {
+                let mut state = State::new(id, string(vertex, "name").unwrap_or(id));
+                state.parent   = parent.map(|x| x.to_string());
+                state.entry    = behaviors(vertex, "entryActivities");
+                state.exit     = behaviors(vertex, "exitActivities");
+                state.location = location;
+                if !array(vertex, "doActivities").is_empty() {
+                    self.diagnostics.warn(self.error(vertex, "doActivities are not supported and were ignored"));
+                }
+                builder.add_state(state);
+                for region in array(vertex, "regions") {
+                    try!(self.read_region(builder, index, region, Some(id)));
+                }
+                Ok(())
+            },
+            "UMLFinalState" => {
+                builder.add_vertex(id, VertexKind::Final, parent.map(|x| x.to_string()), location);
+                Ok(())
+            },
+            "UMLPseudostate" => {
+                // StarUML leaves out the kind of initial pseudostates, it is the default
+                let kind = match string(vertex, "kind").unwrap_or("initial") {
+                    "initial"  => VertexKind::Initial,
+                    "choice"   => VertexKind::Choice,
+                    "junction" => VertexKind::Junction,
+                    x          => return Err(self.error(vertex, format!("unsupported pseudostate kind {}", x)))
+                };
+                builder.add_vertex(id, kind, parent.map(|x| x.to_string()), location);
+                Ok(())
+            },
+            x => Err(self.error(vertex, format!("unsupported vertex type {}", x)))
+        }
+    }
+
+    fn read_transition(&self, builder: &mut ModelBuilder, index: &HashMap<String, &Json>, trans: &Json) -> Result<()> {
+        let id = try!(self.req_str(trans, "_id"));
+        let source = match reference(trans, "source") {
+            Some(x) => x,
+            None    => return Err(self.error(trans, "transition without source"))
+        };
+        let target = match reference(trans, "target") {
+            Some(x) => x,
+            None    => return Err(self.error(trans, "transition without target"))
+        };
+        let mut t = Transition::new(id, source, target);
+        t.guard    = string(trans, "guard").map(|x| x.trim().to_string()).and_then(|x| if x.is_empty() { None } else { Some(x) });
+        t.effect   = behaviors(trans, "effects");
+        t.location = self.locations.get(id).cloned();
+        let triggers = array(trans, "triggers");
+        if triggers.is_empty() {
+            builder.add_transition(t);
+            return Ok(())
+        }
+        // Every trigger of the transition fires it, the same way
+        for trigger in triggers {
+            let mut t = t.clone();
+            t.trigger = Some(try!(self.read_event(index, trigger)));
+            builder.add_transition(t);
+        }
+        Ok(())
+    }
+
+    fn read_event(&self, index: &HashMap<String, &Json>, event: &Json) -> Result<Event> {
+        let id = try!(self.req_str(event, "_id"));
+        let name = match string(event, "name").and_then(|x| if x.is_empty() { None } else { Some(x) }) {
+            Some(x) => x,
+            None    => match reference(event, "targetSignal").and_then(|x| index.get(x)).and_then(|x| string(x, "name")) {
+                Some(x) => x,
+                None    => return Err(self.error(event, "event without name"))
+            }
+        };
+        let location = self.locations.get(id).cloned();
+        match string(event, "kind").unwrap_or("signal") {
+            // Each trigger has its own UMLEvent, signals are told apart by name
+            "signal" | "call" => Ok(Event::signal(name)),
+            "time"            => {
+                let timeout = string(event, "expression").or(string(event, "value")).unwrap_or("");
+                let timeout = timeout.trim();
+                let (relative, timeout) = if timeout.starts_with("at ") {
+                    (false, &timeout[3..])
+                } else if timeout.starts_with("after ") {
+                    (true, &timeout[6..])
+                } else {
+                    (true, timeout)
+                };
+                match parse_duration_ms(timeout) {
+                    Some(ms) => Ok(Event::Time {
+                        id         : id.to_string(),
+                        name       : name.to_string(),
+                        relative   : relative,
+                        timeout_ms : ms,
+                        location   : location,
+                    }),
+                    None     => Err(self.error(event, format!("time event without a valid timeout: {:?}", timeout)))
+                }
+            },
+            "anyReceive"      => Ok(Event::Any),
+            x                 => Err(self.error(event, format!("unsupported event kind {}", x)))
+        }
+    }
+
+    fn req_str<'j>(&self, obj: &'j Json, key: &str) -> Result<&'j str> {
+        match string(obj, key) {
+            Some(x) => Ok(x),
+            None    => Err(self.error(obj, format!("missing {}", key)))
+        }
+    }
+
+    /// An error about `obj`, with its type, id and location filled in
+    pub fn error<S: Into<String>>(&self, obj: &Json, msg: S) -> HsmGenError {
+        let id = string(obj, "_id");
+        HsmGenError::model(msg)
+            .context(string(obj, "_type").unwrap_or(""), id.unwrap_or(""))
+            .with_location(id.and_then(|x| self.locations.get(x)).cloned())
+    }
+}
+
+impl ModelReader for MdjReader {
+    fn read_states(&self) -> Result<HashMap<String, State>> {
+        let mut index = HashMap::new();
+        index_elements(&self.json, &mut index);
+        let mut machines = Vec::new();
+        find_elements(&self.json, "UMLStateMachine", &mut machines);
+        let machine = match machines.first() {
+            Some(x) => *x,
+            None    => return Err(HsmGenError::model(format!("no UMLStateMachine in {}", self.file)))
+        };
+        for other in machines.iter().skip(1) {
+            self.diagnostics.warn(self.error(other, "only the first state machine of the project is read"));
+        }
+        let mut builder = ModelBuilder::new();
+        for region in array(machine, "regions") {
+            try!(self.read_region(&mut builder, &index, region, None));
+        }
+        builder.build(&self.diagnostics)
+    }
+
+    fn diagnostic_log(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+}
+
+fn string<'j>(obj: &'j Json, key: &str) -> Option<&'j str> {
+    obj.find(key).and_then(|x| x.as_string())
+}
+
+fn array<'j>(obj: &'j Json, key: &str) -> &'j [Json] {
+    match obj.find(key).and_then(|x| x.as_array()) {
+        Some(x) => &x[..],
+        None    => &[]
+    }
+}
+
+/// The id a `{"$ref": "<_id>"}` member points to
+fn reference<'j>(obj: &'j Json, key: &str) -> Option<&'j str> {
+    obj.find(key).and_then(|x| string(x, "$ref"))
+}
+
+/// Names of the behaviors in `key`, merged into one block if there are several
+fn behaviors(obj: &Json, key: &str) -> Option<String> {
+    join_code(array(obj, key).iter().filter_map(|x| string(x, "name")).map(|x| x.to_string()).collect())
+}
+
+fn index_elements<'j>(json: &'j Json, index: &mut HashMap<String, &'j Json>) {
+    match *json {
+        Json::Object(ref obj) => {
+            if let Some(id) = json.find("_id").and_then(|x| x.as_string()) {
+                index.insert(id.to_string(), json);
+            }
+            for value in obj.values() {
+                index_elements(value, index);
+            }
+        },
+        Json::Array(ref arr) => for value in arr.iter() {
+            index_elements(value, index);
+        },
+        _ => {}
+    }
+}
+
+fn find_elements<'j>(json: &'j Json, ty: &str, found: &mut Vec<&'j Json>) {
+    if string(json, "_type") == Some(ty) {
+        found.push(json);
+    }
+    for element in array(json, "ownedElements") {
+        find_elements(element, ty, found);
+    }
+}
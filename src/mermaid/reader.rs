@@ -0,0 +1,223 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use std::collections::HashMap;
+
+use ::error::{HsmGenError, Result, Diagnostics};
+use ::location::read_source;
+use ::diagram::{DiagramSource, Diagram, is_ident};
+use ::ir::{State, VertexKind, ModelReader};
+
+
+/// Reads Mermaid `stateDiagram-v2` diagrams.
+///
+/// Composite states are `state X { ... }`, `[*]` is the initial or final
+/// state of the enclosing state and transitions are
+/// `A --> B : event [guard] / effect`. `<<choice>>` states become choices.
+/// Concurrent regions are not supported, so a `<<fork>>` or `<<join>>` state
+/// only passes one transition on and may have only one outgoing transition.
+/// Entry and exit code is given with `X : entry / code` lines or in notes
+/// attached to the state.
+pub struct MermaidReader {
+    source      : DiagramSource,
+    /// Lines of `src` holding the diagram, the end is exclusive
+    lines       : (usize, usize),
+    diagnostics : Diagnostics,
+}
+
+impl MermaidReader {
+    /// Reads a file holding only the diagram, like `.mmd` files
+    pub fn from_file(file: &str) -> Result<Self> {
+        let src = try!(read_source(file));
+        let count = src.lines().count();
+        Ok(MermaidReader {
+            source      : DiagramSource { file: file.to_string(), src: src },
+            lines       : (0, count),
+            diagnostics : Diagnostics::new(),
+        })
+    }
+
+    /// Reads the first ```` ```mermaid ```` fenced block of a Markdown file
+    /// that is a state diagram
+    pub fn from_markdown(file: &str) -> Result<Self> {
+        let src = try!(read_source(file));
+        let mut block = None;
+        let mut start = None;
+        for (i, line) in src.lines().enumerate() {
+            let text = line.trim();
+            match start {
+                None    => if text.starts_with("```") && text[3..].trim() == "mermaid" {
+                    start = Some(i + 1);
+                },
+                Some(s) => if text.starts_with("```") {
+                    let is_state_diagram = src.lines().skip(s).take(i - s)
+                                              .map(|x| x.trim())
+                                              .find(|x| !x.is_empty() && !x.starts_with("%%"))
+                                              .map_or(false, |x| x.starts_with("stateDiagram"));
+                    if is_state_diagram {
+                        block = Some((s, i));
+                        break
+                    }
+                    start = None;
+                },
+            }
+        }
+        match block {
+            Some(lines) => Ok(MermaidReader {
+                source      : DiagramSource { file: file.to_string(), src: src },
+                lines       : lines,
+                diagnostics : Diagnostics::new(),
+            }),
+            None        => Err(HsmGenError::parse(format!("no mermaid stateDiagram block in {}", file)))
+        }
+    }
+}
+
+impl ModelReader for MermaidReader {
+    fn read_states(&self) -> Result<HashMap<String, State>> {
+        let mut diagram = Diagram::new(&self.source, &self.diagnostics);
+        let mut forks = HashMap::new();
+        let (first, end) = self.lines;
+        let mut header = false;
+        for (i, line) in self.source.src.lines().enumerate().skip(first).take(end - first) {
+            let text = line.trim();
+            if diagram.note.is_none() && (text.is_empty() || text.starts_with("%%")) {
+                continue
+            }
+            if !header {
+                if text != "stateDiagram-v2" && text != "stateDiagram" {
+                    return Err(self.source.error(i, text, "a state diagram starts with stateDiagram-v2"))
+                }
+                header = true;
+                continue
+            }
+            if let Err(e) = read_line(&mut diagram, &mut forks, i, text) {
+                try!(self.diagnostics.recover(e));
+            }
+        }
+        diagram.build(end)
+    }
+
+    fn diagnostic_log(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+}
+
+/// Reads line `line`, `forks` counts the outgoing transitions of `<<fork>>` and `<<join>>` states
+fn read_line(d: &mut Diagram, forks: &mut HashMap<String, usize>, line: usize, text: &str) -> Result<()> {
+    if d.note.is_some() {
+        return d.note_line(text, text == "end note")
+    }
+    if text == "}" {
+        return d.close_scope(line, text)
+    }
+    if text == "--" {
+        return Err(d.source.error(line, text, "concurrent regions are not supported"))
+    }
+    if text.starts_with("state ") {
+        return declaration(d, forks, line, text[6..].trim())
+    }
+    if text.starts_with("note ") {
+        return note(d, line, text[5..].trim())
+    }
+    let ignored = ["direction ", "classDef ", "class ", "accTitle", "accDescr", "click "];
+    if ignored.iter().any(|x| text.starts_with(x)) {
+        return Ok(())
+    }
+    let (head, label) = match text.find(':') {
+        // `A:::class` only styles the state
+        Some(x) if text[x..].starts_with(":::") => (text[..x].trim(), None),
+        Some(x)                                 => (text[..x].trim(), Some(text[x + 1..].trim())),
+        None                                    => (text, None)
+    };
+    if let Some(arrow) = head.find("-->") {
+        let source = try!(d.transition(line, head[..arrow].trim(), head[arrow + 3..].trim(), label.unwrap_or("")));
+        if let Some(count) = forks.get_mut(&source) {
+            *count += 1;
+            if *count > 1 {
+                return Err(HsmGenError::model(format!(
+                    "{} has more than one outgoing transition, forks into concurrent regions are not supported", source
+                )).with_location(d.source.location(line, &source)))
+            }
+        }
+        return Ok(())
+    }
+    if is_ident(head) {
+        try!(d.state(line, head));
+        return match label {
+            Some(desc) => d.behavior(head, line, desc),
+            None       => Ok(())
+        }
+    }
+    Err(d.source.error(line, text, format!("unrecognised line: {}", text)))
+}
+
+/// `state X`, `state "Description" as X`, `state X <<choice>>` or `state X {`
+fn declaration(d: &mut Diagram, forks: &mut HashMap<String, usize>, line: usize, decl: &str) -> Result<()> {
+    let (decl, opens) = if decl.ends_with('{') {
+        (decl[..decl.len() - 1].trim(), true)
+    } else {
+        (decl, false)
+    };
+    let (decl, stereotype) = match (decl.find("<<"), decl.find(">>")) {
+        (Some(s), Some(e)) if s < e => (decl[..s].trim(), Some(decl[s + 2..e].trim())),
+        _                           => (decl, None)
+    };
+    let id = match decl.rfind(" as ") {
+        Some(x) if decl.starts_with('"') => decl[x + 4..].trim(),
+        _                                => decl,
+    };
+    if !is_ident(id) {
+        return Err(d.source.error(line, decl, format!("invalid state name {:?}", id)))
+    }
+    match stereotype {
+        Some("choice")              => d.pseudostate(line, id, VertexKind::Choice),
+        // Without concurrent regions a fork or join only passes one transition on
+        Some("fork") | Some("join") => {
+            d.pseudostate(line, id, VertexKind::Junction);
+            forks.insert(id.to_string(), 0);
+        },
+        Some(x)                     => return Err(d.source.error(line, decl, format!("<<{}>> states are not supported", x))),
+        None                        => return d.declare(line, id, opens)
+    }
+    Ok(())
+}
+
+/// `note left of X : entry / code` or a multi line note ending with `end note`
+fn note(d: &mut Diagram, line: usize, note: &str) -> Result<()> {
+    let (head, text) = match note.find(':') {
+        Some(x) => (note[..x].trim(), Some(note[x + 1..].trim())),
+        None    => (note, None)
+    };
+    let id = match head.find(" of ") {
+        Some(x) => head[x + 4..].trim().to_string(),
+        None    => return Err(d.source.error(line, note, "a note must be attached to a state with `of`"))
+    };
+    match text {
+        Some(text) => d.behavior(&id, line, text),
+        None       => {
+            d.note = Some((Some(id), line, Vec::new()));
+            Ok(())
+        }
+    }
+}
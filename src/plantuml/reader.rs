@@ -0,0 +1,215 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use std::collections::HashMap;
+
+use ::error::{Result, Diagnostics};
+use ::location::read_source;
+use ::diagram::{DiagramSource, Diagram, is_ident};
+use ::ir::{State, VertexKind, ModelReader};
+
+
+/// Reads PlantUML state diagrams.
+///
+/// The first `@startuml` block of the file is read, or the whole file if it
+/// has none. States are `state X`, `state X { ... }` or any name used in a
+/// transition, `[*] --> X` is the initial state of the enclosing state and
+/// transitions are `A --> B : event [guard] / effect`. Entry and exit code is
+/// given with `X : entry / code` lines or in notes attached to the state.
+pub struct PlantUmlReader {
+    source      : DiagramSource,
+    diagnostics : Diagnostics,
+}
+
+impl PlantUmlReader {
+    pub fn from_file(file: &str) -> Result<Self> {
+        Ok(PlantUmlReader {
+            source      : DiagramSource { file: file.to_string(), src: try!(read_source(file)) },
+            diagnostics : Diagnostics::new(),
+        })
+    }
+}
+
+impl ModelReader for PlantUmlReader {
+    fn read_states(&self) -> Result<HashMap<String, State>> {
+        let mut diagram = Diagram::new(&self.source, &self.diagnostics);
+        let src = &self.source.src;
+        let has_start = src.lines().any(|x| x.trim().starts_with("@startuml"));
+        let mut started = !has_start;
+        let mut in_comment = false;
+        for (i, line) in src.lines().enumerate() {
+            let text = line.trim();
+            if !started {
+                started = text.starts_with("@startuml");
+                continue
+            }
+            if text.starts_with("@enduml") {
+                break
+            }
+            if in_comment {
+                in_comment = !text.ends_with("'/");
+                continue
+            }
+            if text.starts_with("/'") {
+                in_comment = !text.ends_with("'/") || text.len() < 4;
+                continue
+            }
+            if text.is_empty() || text.starts_with('\'') {
+                continue
+            }
+            if let Err(e) = read_line(&mut diagram, i, text) {
+                try!(self.diagnostics.recover(e));
+            }
+        }
+        diagram.build(src.lines().count())
+    }
+
+    fn diagnostic_log(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+}
+
+fn read_line(d: &mut Diagram, line: usize, text: &str) -> Result<()> {
+    if d.note.is_some() {
+        return d.note_line(text, text.starts_with("end note") || text == "endnote")
+    }
+    if text == "}" {
+        return d.close_scope(line, text)
+    }
+    if text == "--" || text == "||" {
+        return Err(d.source.error(line, text, "concurrent regions are not supported"))
+    }
+    if text.starts_with("state ") {
+        return declaration(d, line, text[6..].trim())
+    }
+    if text.starts_with("note ") {
+        return note(d, line, text[5..].trim())
+    }
+    let (head, label) = match text.find(':') {
+        Some(x) => (text[..x].trim(), Some(text[x + 1..].trim())),
+        None    => (text, None)
+    };
+    if let Some((source, arrow, target)) = split_arrow(head) {
+        if source.ends_with('<') {
+            return Err(d.source.error(line, arrow, "reversed arrows are not supported, write the transition from source to target"))
+        }
+        return d.transition(line, source, target, label.unwrap_or("")).map(|_| ())
+    }
+    if is_ident(head) {
+        try!(d.state(line, head));
+        return match label {
+            Some(desc) => d.behavior(head, line, desc),
+            None       => Ok(())
+        }
+    }
+    let ignored = ["hide ", "skinparam", "title ", "scale ", "left to right", "top to bottom",
+                   "header ", "footer ", "caption ", "legend", "endlegend", "!"];
+    if !ignored.iter().any(|x| text.starts_with(x)) {
+        d.diagnostics.warn(d.source.error(line, text, format!("unrecognised line ignored: {}", text)));
+    }
+    Ok(())
+}
+
+/// `state X`, `state "Long name" as X <<choice>> {` and similar
+fn declaration(d: &mut Diagram, line: usize, decl: &str) -> Result<()> {
+    let (decl, opens) = if decl.ends_with('{') {
+        (decl[..decl.len() - 1].trim(), true)
+    } else {
+        (decl, false)
+    };
+    let (decl, desc) = match decl.find(':') {
+        Some(x) => (decl[..x].trim(), Some(decl[x + 1..].trim())),
+        None    => (decl, None)
+    };
+    let (decl, stereotype) = match (decl.find("<<"), decl.find(">>")) {
+        (Some(s), Some(e)) if s < e => (decl[..s].trim(), Some(decl[s + 2..e].trim())),
+        _                           => (decl, None)
+    };
+    // The id is the name that is not quoted, colors like #pink are left out
+    let id = match decl.find(" as ") {
+        Some(x) if decl.starts_with('"') => decl[x + 4..].trim(),
+        Some(x)                          => decl[..x].trim(),
+        None                             => decl,
+    }.split_whitespace().next().unwrap_or("");
+    if !is_ident(id) {
+        return Err(d.source.error(line, decl, format!("invalid state name {:?}", id)))
+    }
+    match stereotype {
+        Some("choice")   => {
+            d.pseudostate(line, id, VertexKind::Choice);
+            return Ok(())
+        },
+        Some(x)          => return Err(d.source.error(line, decl, format!("<<{}>> states are not supported", x))),
+        None             => {}
+    }
+    try!(d.declare(line, id, false));
+    if let Some(desc) = desc {
+        try!(d.behavior(id, line, desc));
+    }
+    if opens {
+        d.scopes.push(id.to_string());
+    }
+    Ok(())
+}
+
+/// `note left of X : entry / code` or a multi line note ending with `end note`.
+/// A floating `note "text" as N1` is attached to no state and ends on its line.
+fn note(d: &mut Diagram, line: usize, note: &str) -> Result<()> {
+    if note.starts_with('"') {
+        return Ok(())
+    }
+    let (head, text) = match note.find(':') {
+        Some(x) => (note[..x].trim(), Some(note[x + 1..].trim())),
+        None    => (note, None)
+    };
+    let target = match head.find(" of ") {
+        Some(x) => head[x + 4..].split_whitespace().next().map(|x| x.to_string()),
+        None    => None
+    };
+    match text {
+        Some(text) => if let Some(id) = target {
+            for part in text.split("\\n") {
+                try!(d.behavior(&id, line, part));
+            }
+        },
+        None       => d.note = Some((target, line, Vec::new()))
+    }
+    Ok(())
+}
+
+/// Splits `A --> B` (or `A -down-> B`, `A -[#red]-> B`) into source, arrow and target
+fn split_arrow(text: &str) -> Option<(&str, &str, &str)> {
+    let start = match text.find('-') {
+        Some(x) => x,
+        None    => return None
+    };
+    let end = match text[start..].find('>') {
+        Some(x) => start + x + 1,
+        None    => return None
+    };
+    let arrow = &text[start..end];
+    if arrow.contains(char::is_whitespace) {
+        return None
+    }
+    Some((text[..start].trim(), arrow, text[end..].trim()))
+}
@@ -0,0 +1,235 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use std::collections::HashMap;
+
+use sxd_document::Package;
+use sxd_document::dom::Element;
+
+use ::error::{HsmGenError, Result, Diagnostics};
+use ::location::{SourceLocation, index_xml_attr, read_source};
+use ::xml::{parse_document, children_named, descendants_named, first_child, parent_element, enclosing_location, text};
+use ::ir::{State, Transition, Event, ModelBuilder, VertexKind, ModelReader};
+
+
+/// Reads Quantum Leaps QM models.
+///
+/// The first `<statechart>` of the model is read. States are identified by
+/// their name, which QM keeps unique within a class, `<entry>`, `<exit>` and
+/// `<action>` hold the code and `<choice>` elements of a `<tran>` become a
+/// choice pseudostate. Targets are paths relative to the element holding
+/// them, `..` is the parent element and `N` the N-th `<state>` child.
+pub struct QmReader {
+    file        : String,
+    package     : Package,
+    diagnostics : Diagnostics,
+    locations   : HashMap<String, SourceLocation>,
+}
+
+impl QmReader {
+    pub fn from_file(file: &str) -> Result<Self> {
+        let s = try!(read_source(file));
+        Ok(QmReader {
+            file        : file.to_string(),
+            package     : try!(parse_document(file, &s)),
+            diagnostics : Diagnostics::new(),
+            locations   : index_xml_attr(file, &s, "name"),
+        })
+    }
+
+    fn read_state(&self, builder: &mut ModelBuilder, el: Element, parent: Option<&str>) -> Result<()> {
+        let name = try!(self.req_attr(el, "name"));
+        let mut state = State::new(&name, &name);
+        state.parent   = parent.map(|x| x.to_string());
+        state.entry    = code(el, "entry");
+        state.exit     = code(el, "exit");
+        state.location = self.location(el);
+        builder.add_state(state);
+
+        if let Some(init) = first_child(el, "initial") {
+            let initial_id = format!("{}_initial", name);
+            let target = try!(self.target(init));
+            builder.add_vertex(&initial_id, VertexKind::Initial, Some(name.clone()), self.location(init));
+            let mut t = Transition::new(&format!("{}_t", initial_id), &initial_id, &try!(self.req_target(init, target)));
+            t.effect   = code(init, "action");
+            t.location = self.location(init);
+            builder.add_transition(t);
+        }
+        for (i, tran) in children_named(el, "tran").into_iter().enumerate() {
+            if let Err(e) = self.read_tran(builder, tran, &name, parent, i) {
+                try!(self.diagnostics.recover(e));
+            }
+        }
+        if let Some(history) = first_child(el, "history") {
+            try!(self.diagnostics.recover(self.error(history, "history states are not supported")));
+        }
+        for child in children_named(el, "state") {
+            if let Err(e) = self.read_state(builder, child, Some(&name)) {
+                try!(self.diagnostics.recover(e));
+            }
+        }
+        Ok(())
+    }
+
+    fn read_tran(&self, builder: &mut ModelBuilder, el: Element, source: &str, parent: Option<&str>, index: usize) -> Result<()> {
+        let trig = try!(self.req_attr(el, "trig"));
+        let events = trig.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()).map(Event::signal).collect::<Vec<Event>>();
+        let effect  = code(el, "action");
+        let choices = children_named(el, "choice");
+        let target  = match try!(self.target(el)) {
+            Some(x)                      => x,
+            // A transition without target or choices runs its action and stays in the state
+            None if choices.is_empty()   => {
+                for evt in events.into_iter() {
+                    builder.add_internal(source, evt, None, effect.clone());
+                }
+                return Ok(())
+            },
+            None                         => {
+                let choice_id = format!("{}_tran{}_choice", source, index);
+                builder.add_vertex(&choice_id, VertexKind::Choice, parent.map(|x| x.to_string()), self.location(el));
+                try!(self.read_choices(builder, el, &choice_id, parent));
+                choice_id
+            }
+        };
+        for (i, evt) in events.into_iter().enumerate() {
+            let mut t = Transition::new(&format!("{}_tran{}_{}", source, index, i), source, &target);
+            t.effect   = effect.clone();
+            t.trigger  = Some(evt);
+            t.location = self.location(el);
+            builder.add_transition(t);
+        }
+        Ok(())
+    }
+
+    /// The `<choice>` children of `el` as transitions from the choice pseudostate `choice_id`
+    fn read_choices(&self, builder: &mut ModelBuilder, el: Element, choice_id: &str, parent: Option<&str>) -> Result<()> {
+        for (i, choice) in children_named(el, "choice").into_iter().enumerate() {
+            let target = match try!(self.target(choice)) {
+                Some(x) => x,
+                None    => {
+                    if children_named(choice, "choice").is_empty() {
+                        try!(self.diagnostics.recover(self.error(choice, "choice without target is not supported")));
+                        continue
+                    }
+                    let nested_id = format!("{}_{}", choice_id, i);
+                    builder.add_vertex(&nested_id, VertexKind::Choice, parent.map(|x| x.to_string()), self.location(choice));
+                    try!(self.read_choices(builder, choice, &nested_id, parent));
+                    nested_id
+                }
+            };
+            let mut t = Transition::new(&format!("{}_{}_t", choice_id, i), choice_id, &target);
+            t.guard    = first_child(choice, "guard").map(text);
+            t.effect   = code(choice, "action");
+            t.location = self.location(choice);
+            builder.add_transition(t);
+        }
+        Ok(())
+    }
+
+    /// The name of the state the `target` path of `el` points at
+    fn target(&self, el: Element) -> Result<Option<String>> {
+        let path = match el.attribute_value("target") {
+            Some(x) => x,
+            None    => return Ok(None)
+        };
+        let mut cur = el;
+        for part in path.split('/') {
+            let next = match part {
+                ".." => parent_element(cur),
+                n    => n.parse::<usize>().ok().and_then(|n|
+                    if n == 0 { None } else { children_named(cur, "state").into_iter().nth(n - 1) }
+                )
+            };
+            cur = match next {
+                Some(x) => x,
+                None    => return Err(self.error(el, format!("target {:?} does not lead to a state", path)))
+            };
+        }
+        if cur.name().local_part() != "state" {
+            return Err(self.error(el, format!("target {:?} does not lead to a state", path)))
+        }
+        self.req_attr(cur, "name").map(Some)
+    }
+
+    fn req_target(&self, el: Element, target: Option<String>) -> Result<String> {
+        match target {
+            Some(x) => Ok(x),
+            None    => Err(self.error(el, format!("{} without target", el.name().local_part())))
+        }
+    }
+
+    fn req_attr(&self, el: Element, name: &str) -> Result<String> {
+        match el.attribute_value(name) {
+            Some(x) => Ok(x.to_string()),
+            None    => Err(self.error(el, format!("missing {}", name)))
+        }
+    }
+
+    /// Where `el` or the nearest enclosing state is
+    fn location(&self, el: Element) -> Option<SourceLocation> {
+        enclosing_location(el, "name", &self.locations)
+    }
+
+    /// An error about `el`, with its name and location filled in
+    pub fn error<S: Into<String>>(&self, el: Element, msg: S) -> HsmGenError {
+        HsmGenError::model(msg)
+            .context(el.name().local_part(), el.attribute_value("name").unwrap_or(""))
+            .with_location(self.location(el))
+    }
+}
+
+impl ModelReader for QmReader {
+    fn read_states(&self) -> Result<HashMap<String, State>> {
+        let doc = self.package.as_document();
+        let root = match doc.root().children().into_iter().filter_map(|x| x.element()).next() {
+            Some(x) if x.name().local_part() == "model" => x,
+            _ => return Err(HsmGenError::parse(format!("{} is not a QM model", self.file)))
+        };
+        let mut charts = Vec::new();
+        descendants_named(root, "statechart", &mut charts);
+        let chart = match charts.first() {
+            Some(x) => *x,
+            None    => return Err(HsmGenError::model(format!("no statechart in {}", self.file)))
+        };
+        for other in charts.iter().skip(1) {
+            self.diagnostics.warn(self.error(*other, "only the first statechart of the model is read"));
+        }
+        let mut builder = ModelBuilder::new();
+        for state in children_named(chart, "state") {
+            if let Err(e) = self.read_state(&mut builder, state, None) {
+                try!(self.diagnostics.recover(e));
+            }
+        }
+        builder.build(&self.diagnostics)
+    }
+
+    fn diagnostic_log(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+}
+
+/// The code in the `kind` child of `el`
+fn code(el: Element, kind: &str) -> Option<String> {
+    first_child(el, kind).map(text).and_then(|x| if x.is_empty() { None } else { Some(x) })
+}
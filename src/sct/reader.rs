@@ -0,0 +1,234 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use std::collections::HashMap;
+
+use sxd_document::Package;
+use sxd_document::dom::Element;
+
+use ::error::{HsmGenError, Result, Diagnostics};
+use ::location::{SourceLocation, index_xml_ids, read_source};
+use ::xml::{parse_document, children_named, attribute, parent_element, enclosing_location};
+use ::ir::{State, Transition, Event, Label, ModelBuilder, VertexKind, join_code, ModelReader};
+
+
+/// Reads YAKINDU / itemis CREATE `.sct` statecharts.
+///
+/// The model is EMF XMI with the behavior in YAKINDU's expression language:
+/// transitions have a `trigger [guard] / action` specification, where the
+/// trigger may be `after 5 s` or a list of events, and states have
+/// `entry / action`, `exit / action` and `trigger [guard] / action` lines.
+/// Actions and guards are taken as Rust code.
+pub struct SctReader {
+    file        : String,
+    package     : Package,
+    diagnostics : Diagnostics,
+    locations   : HashMap<String, SourceLocation>,
+}
+
+impl SctReader {
+    pub fn from_file(file: &str) -> Result<Self> {
+        let s = try!(read_source(file));
+        Ok(SctReader {
+            file        : file.to_string(),
+            package     : try!(parse_document(file, &s)),
+            diagnostics : Diagnostics::new(),
+            locations   : index_xml_ids(file, &s),
+        })
+    }
+
+    fn read_region(&self, builder: &mut ModelBuilder, region: Element, parent: Option<&str>) -> Result<()> {
+        for vertex in children_named(region, "vertices") {
+            if let Err(e) = self.read_vertex(builder, vertex, parent) {
+                try!(self.diagnostics.recover(e));
+            }
+        }
+        Ok(())
+    }
+
+    fn read_vertex(&self, builder: &mut ModelBuilder, el: Element, parent: Option<&str>) -> Result<()> {
+        let id = try!(self.req_attr(el, "id"));
+        let ty = attribute(el, "type").unwrap_or("");
+        let ty = ty.rsplit(':').next().unwrap_or(ty);
+        let kind = match ty {
+            "State"       => None,
+            "Choice"      => Some(VertexKind::Choice),
+            "FinalState"  => Some(VertexKind::Final),
+            "Entry"       => match attribute(el, "kind") {
+                None | Some("INITIAL") => Some(VertexKind::Initial),
+                Some(x)                => return Err(self.error(el, format!("{} entries are not supported", x)))
+            },
+            x             => return Err(self.error(el, format!("{} vertices are not supported", x)))
+        };
+        match kind {
+            Some(kind) => builder.add_vertex(&id, kind, parent.map(|x| x.to_string()), self.location(el)),
+            None       => {
+                let mut state = State::new(&id, attribute(el, "name").unwrap_or(&id));
+                state.parent   = parent.map(|x| x.to_string());
+                state.location = self.location(el);
+                builder.add_state(state);
+                if let Some(spec) = attribute(el, "specification") {
+                    try!(self.read_state_spec(builder, el, &id, spec));
+                }
+            }
+        }
+        for (i, t_el) in children_named(el, "outgoingTransitions").into_iter().enumerate() {
+            if let Err(e) = self.read_transition(builder, t_el, &id, i) {
+                try!(self.diagnostics.recover(e));
+            }
+        }
+        for region in children_named(el, "regions") {
+            try!(self.read_region(builder, region, Some(&id)));
+        }
+        Ok(())
+    }
+
+    /// `entry / action`, `exit / action` and local reactions of state `id`
+    fn read_state_spec(&self, builder: &mut ModelBuilder, el: Element, id: &str, spec: &str) -> Result<()> {
+        let mut entry = Vec::new();
+        let mut exit  = Vec::new();
+        for line in spec.lines().map(|x| x.trim()).filter(|x| !x.is_empty()) {
+            let label = Label::parse(line);
+            let trigger = match label.trigger {
+                Some(ref x) => x.clone(),
+                None        => {
+                    try!(self.diagnostics.recover(self.error(el, format!("reaction without trigger: {}", line))));
+                    continue
+                }
+            };
+            match trigger.as_str() {
+                "entry" => entry.extend(label.effect),
+                "exit"  => exit.extend(label.effect),
+                _       => for evt in try!(self.events(el, &trigger, attribute(el, "name").unwrap_or(id))).into_iter() {
+                    builder.add_internal(id, evt, label.guard.clone(), label.effect.clone());
+                }
+            }
+        }
+        if let Some(state) = builder.state_mut(id) {
+            state.entry = join_code(entry);
+            state.exit  = join_code(exit);
+        }
+        Ok(())
+    }
+
+    fn read_transition(&self, builder: &mut ModelBuilder, el: Element, source: &str, index: usize) -> Result<()> {
+        let id = attribute(el, "id").map(|x| x.to_string()).unwrap_or(format!("{}_t{}", source, index));
+        let target = try!(self.req_attr(el, "target"));
+        let mut label = Label::parse(attribute(el, "specification").unwrap_or(""));
+        let state  = parent_element(el).and_then(|x| attribute(x, "name")).unwrap_or(source);
+        // The default transition out of a choice is written `else` or `default`
+        let default = label.trigger.as_ref().map_or(false, |x| x == "else" || x == "default");
+        if default && builder.vertex_kind(source) == Some(VertexKind::Choice) {
+            if label.guard.is_some() {
+                return Err(self.error(el, "the default transition of a choice cannot have a guard"))
+            }
+            label.trigger = None;
+            label.guard   = Some("else".to_string());
+        }
+        let events = match label.trigger {
+            Some(ref trigger) => try!(self.events(el, trigger, state)),
+            None              => Vec::new()
+        };
+        let mut t = Transition::new(&id, source, &target);
+        t.guard    = label.guard.clone();
+        t.effect   = label.effect.clone();
+        t.location = self.location(el);
+        if events.is_empty() {
+            builder.add_transition(t);
+            return Ok(())
+        }
+        // Every event of the trigger list fires the transition, the same way
+        for (i, evt) in events.into_iter().enumerate() {
+            let mut t = t.clone();
+            if i > 0 {
+                t.id = format!("{}_{}", id, i);
+            }
+            t.trigger = Some(evt);
+            builder.add_transition(t);
+        }
+        Ok(())
+    }
+
+    /// The events of a trigger list like `Open, door.Close` or `after 30 s` in
+    /// `state`, `else` and `default` only mean something out of a choice
+    fn events(&self, el: Element, trigger: &str, state: &str) -> Result<Vec<Event>> {
+        let mut events = Vec::new();
+        for part in trigger.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+            if part == "default" || part == "else" {
+                return Err(self.error(el, format!("trigger {:?} is only allowed out of a choice", part)))
+            }
+            if part.starts_with("every ") || part == "always" || part == "oncycle" {
+                return Err(self.error(el, format!("trigger {:?} is not supported", part)))
+            }
+            // Events of named interfaces are qualified with the interface name
+            let name = part.rsplit('.').next().unwrap_or(part);
+            events.push(try!(Event::from_trigger(if part.starts_with("after ") { part } else { name }, state, self.location(el))));
+        }
+        Ok(events)
+    }
+
+    fn req_attr(&self, el: Element, name: &str) -> Result<String> {
+        match attribute(el, name) {
+            Some(x) => Ok(x.to_string()),
+            None    => Err(self.error(el, format!("missing {}", name)))
+        }
+    }
+
+    /// Where `el` or the nearest enclosing element with an id is
+    fn location(&self, el: Element) -> Option<SourceLocation> {
+        enclosing_location(el, "id", &self.locations)
+    }
+
+    /// An error about `el`, with its type, id and location filled in
+    pub fn error<S: Into<String>>(&self, el: Element, msg: S) -> HsmGenError {
+        HsmGenError::model(msg)
+            .context(attribute(el, "type").unwrap_or(el.name().local_part()), attribute(el, "id").unwrap_or(""))
+            .with_location(self.location(el))
+    }
+}
+
+impl ModelReader for SctReader {
+    fn read_states(&self) -> Result<HashMap<String, State>> {
+        let doc = self.package.as_document();
+        let mut charts = Vec::new();
+        for el in doc.root().children().into_iter().filter_map(|x| x.element()) {
+            if el.name().local_part() == "Statechart" {
+                charts.push(el);
+            }
+            charts.extend(children_named(el, "Statechart"));
+        }
+        let chart = match charts.first() {
+            Some(x) => *x,
+            None    => return Err(HsmGenError::model(format!("no Statechart in {}", self.file)))
+        };
+        let mut builder = ModelBuilder::new();
+        for region in children_named(chart, "regions") {
+            try!(self.read_region(&mut builder, region, None));
+        }
+        builder.build(&self.diagnostics)
+    }
+
+    fn diagnostic_log(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+}
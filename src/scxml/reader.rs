@@ -0,0 +1,235 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use std::collections::HashMap;
+
+use sxd_document::Package;
+use sxd_document::dom::Element;
+
+use ::error::{HsmGenError, Result, Diagnostics};
+use ::location::{SourceLocation, index_xml_ids, read_source};
+use ::xml::{parse_document, child_elements, children_named, first_child, enclosing_location, text};
+use ::ir::{State, Transition, Event, ModelBuilder, VertexKind, join_code, ModelReader};
+
+
+/// Reads W3C SCXML state charts.
+///
+/// `<state>`, `<parallel>` and `<final>` become states, `<initial>` children
+/// and `initial` attributes become initial transitions and the `<script>`
+/// content of `<onentry>`, `<onexit>` and `<transition>` is taken as the entry,
+/// exit and effect code. Rust-hsm states have a single region, so a
+/// `<parallel>` may only have one child state.
+pub struct ScxmlReader {
+    file        : String,
+    package     : Package,
+    diagnostics : Diagnostics,
+    locations   : HashMap<String, SourceLocation>,
+}
+
+impl ScxmlReader {
+    pub fn from_file(file: &str) -> Result<Self> {
+        let s = try!(read_source(file));
+        let package = try!(parse_document(file, &s));
+        Ok(ScxmlReader {
+            file        : file.to_string(),
+            package     : package,
+            diagnostics : Diagnostics::new(),
+            locations   : index_xml_ids(file, &s),
+        })
+    }
+
+    /// Reads the states in `el`, `parent` is the id of the state `el` is
+    fn read_children(&self, builder: &mut ModelBuilder, el: Element, parent: Option<&str>) -> Result<()> {
+        for (i, child) in child_elements(el).into_iter().enumerate() {
+            let res = match child.name().local_part() {
+                "state" | "parallel" => self.read_state(builder, child, parent, i),
+                "final"              => {
+                    let id = element_id(child, parent, i);
+                    if child_elements(child).iter().any(|x| x.name().local_part() == "onentry" || x.name().local_part() == "onexit") {
+                        self.diagnostics.warn(self.error(child, "onentry and onexit of final states are ignored"));
+                    }
+                    builder.add_vertex(&id, VertexKind::Final, parent.map(|x| x.to_string()), self.location(child));
+                    Ok(())
+                },
+                "history" | "invoke" => Err(self.error(child, format!("<{}> is not supported", child.name().local_part()))),
+                _                    => Ok(())
+            };
+            if let Err(e) = res {
+                try!(self.diagnostics.recover(e));
+            }
+        }
+        Ok(())
+    }
+
+    fn read_state(&self, builder: &mut ModelBuilder, el: Element, parent: Option<&str>, index: usize) -> Result<()> {
+        let id = element_id(el, parent, index);
+        let mut state = State::new(&id, &id);
+        state.parent   = parent.map(|x| x.to_string());
+        state.entry    = self.executable(el, "onentry");
+        state.exit     = self.executable(el, "onexit");
+        state.location = self.location(el);
+        builder.add_state(state);
+
+        let substates = child_elements(el).into_iter().enumerate().filter(|&(_, x)|
+            match x.name().local_part() {
+                "state" | "parallel" | "final" => true,
+                _                              => false
+            }
+        ).map(|(i, x)| element_id(x, Some(&id), i)).collect::<Vec<String>>();
+        if el.name().local_part() == "parallel" && substates.len() > 1 {
+            try!(self.diagnostics.recover(self.error(el, format!("parallel state has {} regions, only one is supported", substates.len()))));
+        }
+
+        // The initial state is the <initial> child, the initial attribute or the first child state
+        let initial_id = format!("{}_initial", id);
+        let initial_el = first_child(el, "initial");
+        let initial = match (initial_el, el.attribute_value("initial")) {
+            (Some(init), _) => match first_child(init, "transition") {
+                Some(t_el) => match try!(self.target(t_el)) {
+                    Some(target) => {
+                        let mut t = Transition::new(&format!("{}_t", initial_id), &initial_id, &target);
+                        t.effect = self.effect(t_el);
+                        Some(t)
+                    },
+                    None         => return Err(self.error(t_el, "initial transition without target"))
+                },
+                None       => return Err(self.error(init, "initial without transition"))
+            },
+            (None, Some(target)) => {
+                let targets = target.split_whitespace().collect::<Vec<&str>>();
+                if targets.len() != 1 {
+                    return Err(self.error(el, format!("initial has {} targets, expected 1", targets.len())))
+                }
+                Some(Transition::new(&format!("{}_t", initial_id), &initial_id, targets[0]))
+            },
+            (None, None) => substates.first().map(|x| Transition::new(&format!("{}_t", initial_id), &initial_id, x)),
+        };
+        if let Some(t) = initial {
+            builder.add_vertex(&initial_id, VertexKind::Initial, Some(id.clone()), self.location(el));
+            builder.add_transition(t);
+        }
+
+        for (i, t_el) in children_named(el, "transition").into_iter().enumerate() {
+            if let Err(e) = self.read_transition(builder, t_el, &id, i) {
+                try!(self.diagnostics.recover(e));
+            }
+        }
+        self.read_children(builder, el, Some(&id))
+    }
+
+    fn read_transition(&self, builder: &mut ModelBuilder, el: Element, source: &str, index: usize) -> Result<()> {
+        let guard  = el.attribute_value("cond").map(|x| x.trim().to_string());
+        let effect = self.effect(el);
+        let events = el.attribute_value("event").unwrap_or("").split_whitespace().map(event).collect::<Vec<Event>>();
+        if events.is_empty() {
+            return Err(self.error(el, "transitions without event are not supported"))
+        }
+        match try!(self.target(el)) {
+            Some(target) => for (i, evt) in events.into_iter().enumerate() {
+                let mut t = Transition::new(&format!("{}_t{}_{}", source, index, i), source, &target);
+                t.guard    = guard.clone();
+                t.effect   = effect.clone();
+                t.trigger  = Some(evt);
+                t.location = self.location(el);
+                builder.add_transition(t);
+            },
+            // A transition without target runs its content and stays in the state
+            None         => for evt in events.into_iter() {
+                builder.add_internal(source, evt, guard.clone(), effect.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn target(&self, el: Element) -> Result<Option<String>> {
+        let targets = el.attribute_value("target").unwrap_or("").split_whitespace().collect::<Vec<&str>>();
+        match targets.len() {
+            0 => Ok(None),
+            1 => Ok(Some(targets[0].to_string())),
+            n => Err(self.error(el, format!("transition has {} targets, expected 1", n)))
+        }
+    }
+
+    /// The code of the `kind` (onentry or onexit) children of `el`
+    fn executable(&self, el: Element, kind: &str) -> Option<String> {
+        join_code(children_named(el, kind).into_iter().filter_map(|x| self.effect(x)).collect())
+    }
+
+    /// The `<script>` content of `el`, other executable content is not supported
+    fn effect(&self, el: Element) -> Option<String> {
+        let mut code = Vec::new();
+        for child in child_elements(el).into_iter() {
+            match child.name().local_part() {
+                "script" => code.push(text(child)),
+                x        => self.diagnostics.warn(self.error(child, format!("<{}> is not supported and was ignored, use <script>", x))),
+            }
+        }
+        join_code(code)
+    }
+
+    /// Where `el` or the nearest enclosing element with an id is
+    fn location(&self, el: Element) -> Option<SourceLocation> {
+        enclosing_location(el, "id", &self.locations)
+    }
+
+    /// An error about `el`, with its name, id and location filled in
+    pub fn error<S: Into<String>>(&self, el: Element, msg: S) -> HsmGenError {
+        HsmGenError::model(msg)
+            .context(el.name().local_part(), el.attribute_value("id").unwrap_or(""))
+            .with_location(self.location(el))
+    }
+}
+
+impl ModelReader for ScxmlReader {
+    fn read_states(&self) -> Result<HashMap<String, State>> {
+        let doc = self.package.as_document();
+        let root = match doc.root().children().into_iter().filter_map(|x| x.element()).next() {
+            Some(x) if x.name().local_part() == "scxml" => x,
+            _ => return Err(HsmGenError::parse(format!("{} is not an SCXML document", self.file)))
+        };
+        let mut builder = ModelBuilder::new();
+        try!(self.read_children(&mut builder, root, None));
+        builder.build(&self.diagnostics)
+    }
+
+    fn diagnostic_log(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+}
+
+/// The id of a state, SCXML ids are optional so missing ones are made up from the position
+fn element_id(el: Element, parent: Option<&str>, index: usize) -> String {
+    match el.attribute_value("id") {
+        Some(id) => id.to_string(),
+        None     => format!("{}_{}{}", parent.unwrap_or("scxml"), el.name().local_part(), index)
+    }
+}
+
+/// An SCXML event descriptor, `*` matches any event and `foo.*` is the same as `foo`
+fn event(descriptor: &str) -> Event {
+    let name = descriptor.trim_end_matches(".*").trim_end_matches('.');
+    match name {
+        "*" | "" => Event::Any,
+        _        => Event::signal(name)
+    }
+}
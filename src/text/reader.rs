@@ -0,0 +1,263 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use std::path::Path;
+use std::collections::{HashMap, BTreeMap};
+
+use rustc_serialize::json::Json;
+use toml;
+use yaml_rust::{Yaml, YamlLoader};
+
+use ::error::{HsmGenError, Result, Diagnostics};
+use ::location::{SourceLocation, read_source};
+use ::ir::{State, Transition, Event, ModelBuilder, VertexKind, parse_duration_ms, join_code, ModelReader};
+
+
+const STATE_KEYS      : &'static [&'static str] = &["parent", "entry", "exit", "initial", "transitions"];
+const CHOICE_KEYS     : &'static [&'static str] = &["parent", "transitions"];
+const TRANSITION_KEYS : &'static [&'static str] = &["event", "after", "when", "guard", "effect", "target"];
+
+/// Reads machines written in the hsm-gen text format, as TOML or YAML.
+///
+/// The `states` table has a table for every state with its `parent`,
+/// `entry` and `exit` code, the `initial` substate and a list of
+/// `transitions`. A transition has an `event` (or `after` for a timeout),
+/// a `guard`, an `effect` and a `target`, without target it is internal.
+/// The `choices` table has the choice points, whose transitions only have
+/// guards. See the README for an example.
+pub struct TextReader {
+    file        : String,
+    doc         : Json,
+    diagnostics : Diagnostics,
+}
+
+impl TextReader {
+    /// Reads `.toml` files as TOML and `.yaml` or `.yml` files as YAML
+    pub fn from_file(file: &str) -> Result<Self> {
+        let s = try!(read_source(file));
+        let ext = Path::new(file).extension().and_then(|x| x.to_str()).unwrap_or("").to_lowercase();
+        let doc = match ext.as_str() {
+            "toml"         => try!(parse_toml(file, &s)),
+            "yaml" | "yml" => try!(parse_yaml(file, &s)),
+            _              => return Err(HsmGenError::parse(format!("{} is not a .toml or .yaml file", file)))
+        };
+        Ok(TextReader {
+            file        : file.to_string(),
+            doc         : doc,
+            diagnostics : Diagnostics::new(),
+        })
+    }
+
+    fn read_state(&self, builder: &mut ModelBuilder, name: &str, st: &Json) -> Result<()> {
+        if st.as_object().is_none() {
+            return Err(error("state", name, "a state must be a table"))
+        }
+        self.check_keys(st, STATE_KEYS, "state", name);
+        let mut state = State::new(name, name);
+        state.parent = string(st, "parent");
+        state.entry  = code(st, "entry");
+        state.exit   = code(st, "exit");
+        builder.add_state(state);
+        if let Some(initial) = string(st, "initial") {
+            let initial_id = format!("{}_initial", name);
+            builder.add_vertex(&initial_id, VertexKind::Initial, Some(name.to_string()), None);
+            builder.add_transition(Transition::new(&format!("{}_t", initial_id), &initial_id, &initial));
+        }
+        for (i, t) in array(st, "transitions").iter().enumerate() {
+            if let Err(e) = self.read_transition(builder, name, i, t) {
+                try!(self.diagnostics.recover(e));
+            }
+        }
+        Ok(())
+    }
+
+    fn read_transition(&self, builder: &mut ModelBuilder, source: &str, index: usize, t: &Json) -> Result<()> {
+        let id = format!("{}.transitions[{}]", source, index);
+        if t.as_object().is_none() {
+            return Err(error("transition", &id, "a transition must be a table"))
+        }
+        self.check_keys(t, TRANSITION_KEYS, "transition", &id);
+        let when = string(t, "when");
+        if when.is_some() && (t.find("event").is_some() || t.find("after").is_some()) {
+            return Err(error("transition", &id, "a transition has an event, a timeout or a condition, not several"))
+        }
+        let event = match (string(t, "event"), t.find("after")) {
+            (Some(_), Some(_))          => return Err(error("transition", &id, "a transition has an event or a timeout, not both")),
+            (Some(ref x), None) if x == "*" => Some(Event::Any),
+            (Some(x), None)             => Some(Event::signal(&x)),
+            (None, Some(after))         => {
+                // Numbers are milliseconds, strings may have a unit. Numbers that
+                // do not fit a u32 are invalid like strings that do not.
+                let max = ::std::u32::MAX;
+                let ms = match *after {
+                    Json::I64(x) if x >= 0 && x <= max as i64 => Some(x as u32),
+                    Json::U64(x) if x <= max as u64           => Some(x as u32),
+                    Json::String(ref x)                       => parse_duration_ms(x),
+                    _                                         => None
+                };
+                match ms {
+                    Some(ms) => Some(Event::after(source, ms, None)),
+                    None     => return Err(error("transition", &id, format!("invalid timeout {}", after)))
+                }
+            },
+            (None, None)                => when.map(|x| Event::change(&x, None))
+        };
+        let guard  = string(t, "guard");
+        let effect = code(t, "effect");
+        match string(t, "target") {
+            Some(target) => {
+                let mut tr = Transition::new(&id, source, &target);
+                tr.guard   = guard;
+                tr.effect  = effect;
+                tr.trigger = event;
+                builder.add_transition(tr);
+            },
+            None         => match event {
+                Some(evt) => builder.add_internal(source, evt, guard, effect),
+                None      => return Err(error("transition", &id, "a transition without target needs an event"))
+            }
+        }
+        Ok(())
+    }
+
+    /// Warns about keys of `obj` that are not in `known`, most likely typos
+    fn check_keys(&self, obj: &Json, known: &[&str], element: &str, id: &str) {
+        if let Some(obj) = obj.as_object() {
+            for key in obj.keys().filter(|x| !known.contains(&x.as_str())) {
+                self.diagnostics.warn(error(element, id, format!("unknown key {:?} ignored", key)));
+            }
+        }
+    }
+}
+
+impl ModelReader for TextReader {
+    fn read_states(&self) -> Result<HashMap<String, State>> {
+        let states = match self.doc.find("states").and_then(|x| x.as_object()) {
+            Some(x) => x,
+            None    => return Err(HsmGenError::model(format!("{} has no states table", self.file)))
+        };
+        let mut builder = ModelBuilder::new();
+        for (name, st) in states.iter() {
+            if let Err(e) = self.read_state(&mut builder, name, st) {
+                try!(self.diagnostics.recover(e));
+            }
+        }
+        if let Some(choices) = self.doc.find("choices").and_then(|x| x.as_object()) {
+            for (name, ch) in choices.iter() {
+                self.check_keys(ch, CHOICE_KEYS, "choice", name);
+                builder.add_vertex(name, VertexKind::Choice, string(ch, "parent"), None);
+                for (i, t) in array(ch, "transitions").iter().enumerate() {
+                    if let Err(e) = self.read_transition(&mut builder, name, i, t) {
+                        try!(self.diagnostics.recover(e));
+                    }
+                }
+            }
+        }
+        builder.build(&self.diagnostics)
+    }
+
+    fn diagnostic_log(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+}
+
+fn string(obj: &Json, key: &str) -> Option<String> {
+    obj.find(key).and_then(|x| x.as_string()).map(|x| x.to_string())
+}
+
+fn array<'j>(obj: &'j Json, key: &str) -> &'j [Json] {
+    match obj.find(key).and_then(|x| x.as_array()) {
+        Some(x) => &x[..],
+        None    => &[]
+    }
+}
+
+/// Code is a string or a list of strings, which are joined into one block
+fn code(obj: &Json, key: &str) -> Option<String> {
+    match obj.find(key) {
+        Some(&Json::String(ref x)) => Some(x.clone()),
+        Some(&Json::Array(ref x))  => join_code(x.iter().filter_map(|x| x.as_string()).map(|x| x.to_string()).collect()),
+        _                          => None
+    }
+}
+
+fn error<S: Into<String>>(element: &str, id: &str, msg: S) -> HsmGenError {
+    HsmGenError::model(msg).context(element, id)
+}
+
+fn parse_toml(file: &str, src: &str) -> Result<Json> {
+    let mut parser = toml::Parser::new(src);
+    match parser.parse() {
+        Some(table) => Ok(toml_to_json(&toml::Value::Table(table))),
+        None        => {
+            let err = &parser.errors[0];
+            Err(HsmGenError::parse(format!("could not parse {}: {}", file, err.desc))
+                            .with_location(Some(SourceLocation::from_byte(file, src, err.lo))))
+        }
+    }
+}
+
+fn toml_to_json(value: &toml::Value) -> Json {
+    match *value {
+        toml::Value::String(ref x)   => Json::String(x.clone()),
+        toml::Value::Integer(x)      => Json::I64(x),
+        toml::Value::Float(x)        => Json::F64(x),
+        toml::Value::Boolean(x)      => Json::Boolean(x),
+        toml::Value::Datetime(ref x) => Json::String(x.clone()),
+        toml::Value::Array(ref x)    => Json::Array(x.iter().map(toml_to_json).collect()),
+        toml::Value::Table(ref x)    => Json::Object(x.iter().map(|(k, v)| (k.clone(), toml_to_json(v))).collect()),
+    }
+}
+
+fn parse_yaml(file: &str, src: &str) -> Result<Json> {
+    let docs = try!(YamlLoader::load_from_str(src).map_err(|e| {
+        let marker = e.marker();
+        HsmGenError::parse(format!("could not parse {}: {}", file, e))
+                    .with_location(Some(SourceLocation::from_line_col(file, src, marker.line(), marker.col() + 1)))
+    }));
+    match docs.first() {
+        Some(doc) => Ok(yaml_to_json(doc)),
+        None      => Err(HsmGenError::parse(format!("{} is empty", file)))
+    }
+}
+
+fn yaml_to_json(value: &Yaml) -> Json {
+    match *value {
+        Yaml::Real(ref x)    => x.parse::<f64>().map(Json::F64).unwrap_or(Json::String(x.clone())),
+        Yaml::Integer(x)     => Json::I64(x),
+        Yaml::String(ref x)  => Json::String(x.clone()),
+        Yaml::Boolean(x)     => Json::Boolean(x),
+        Yaml::Array(ref x)   => Json::Array(x.iter().map(yaml_to_json).collect()),
+        Yaml::Hash(ref x)    => Json::Object(x.iter().filter_map(|(k, v)| {
+            let key = match *k {
+                Yaml::String(ref s)  => s.clone(),
+                Yaml::Integer(i)     => i.to_string(),
+                Yaml::Real(ref s)    => s.clone(),
+                Yaml::Boolean(b)     => b.to_string(),
+                _                    => return None
+            };
+            Some((key, yaml_to_json(v)))
+        }).collect::<BTreeMap<String, Json>>()),
+        _                    => Json::Null,
+    }
+}
@@ -0,0 +1,64 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use sxd_document::Package;
+use sxd_xpath::{Value, Factory, Context};
+use super::reader::evaluate;
+
+
+/// Exporter specific layout of an XMI document
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Dialect {
+    /// OMG XMI as written by Papyrus and most other tools
+    Generic,
+    /// Sparx Systems Enterprise Architect XMI 2.1 export. Keeps behavior code
+    /// in the `xmi:Extension` block and refers to triggers by `xmi:idref`.
+    EnterpriseArchitect,
+    /// No Magic MagicDraw and Cameo Systems Modeler. Writes references as child
+    /// elements with `xmi:idref` and keeps opaque behaviors in `ownedBehavior`.
+    MagicDraw,
+}
+
+impl Dialect {
+    /// Looks at the `xmi:Documentation` and `xmi:Extension` elements, which name the exporting tool
+    pub fn detect(package: &Package) -> Self {
+        let (factory, context) = (Factory::new(), Context::new());
+        let exporters = ["/*/*[local-name()='Documentation']/@exporter",
+                         "/*/*[local-name()='Documentation']/*[local-name()='exporter']",
+                         "/*/*[local-name()='Extension']/@extender"];
+        for xpath in exporters.iter() {
+            if let Ok(Value::Nodeset(ns)) = evaluate(&factory, &context, package.as_document().root(), xpath) {
+                for node in ns.iter() {
+                    let exporter = node.string_value();
+                    if exporter.starts_with("Enterprise Architect") {
+                        return Dialect::EnterpriseArchitect
+                    }
+                    if exporter.starts_with("MagicDraw") || exporter.starts_with("Cameo") {
+                        return Dialect::MagicDraw
+                    }
+                }
+            }
+        }
+        Dialect::Generic
+    }
+}
@@ -0,0 +1,224 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use std::ops::Deref;
+use std::collections::HashMap;
+
+use sxd_xpath::nodeset::Node;
+
+use ::error::Result;
+use super::XmiModel;
+use super::dialect::Dialect;
+use super::namespaces::is_profile_namespace;
+
+
+/// The elements of a model by id, its transitions by source, its signal events
+/// by name, the Enterprise Architect extension elements and the RustHsm
+/// stereotype applications by the element they extend. Built in one pass
+/// over the documents when reading, so that lookups do not search them again.
+/// Everything else is done by the model it derefs to.
+///
+/// Transitions are not indexed by target, nothing looks them up that way: a
+/// target is an element by id, and the states read resolve the transitions.
+pub struct XmiIndex<'d> {
+    reader      : &'d XmiModel<'d>,
+    elements    : HashMap<String, Node<'d>>,
+    transitions : HashMap<String, Vec<Node<'d>>>,
+    signals     : HashMap<String, Node<'d>>,
+    extensions  : HashMap<String, Node<'d>>,
+    stereotypes : HashMap<String, Vec<Node<'d>>>,
+}
+
+impl<'d> XmiIndex<'d> {
+    pub fn new(reader: &'d XmiModel<'d>) -> Result<Self> {
+        let mut index = XmiIndex {
+            reader      : reader,
+            elements    : HashMap::new(),
+            transitions : HashMap::new(),
+            signals     : HashMap::new(),
+            extensions  : HashMap::new(),
+            stereotypes : HashMap::new(),
+        };
+        try!(index.add_document(reader.root(), None));
+        for (path, root) in reader.references() {
+            try!(index.add_document(root, Some(path)));
+        }
+        Ok(index)
+    }
+
+    /// Indexes the elements of the document `root`, those of a referred to
+    /// document under `<canonical path>#id`. Only transitions of the model
+    /// itself are indexed.
+    fn add_document(&mut self, root: Node<'d>, path: Option<&str>) -> Result<()> {
+        let reader = self.reader;
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            if let Node::Element(elem) = node {
+                if let Some(id) = reader.get_attr(node, "id") {
+                    let key = match path {
+                        Some(path) => format!("{}#{}", path, id),
+                        None       => id
+                    };
+                    self.elements.entry(key).or_insert(node);
+                }
+                if reader.get_type(node).as_ref().map(|x| x.as_str()) == Some("uml:SignalEvent") {
+                    if let Some(name) = reader.get_attr(node, "name") {
+                        self.signals.entry(name).or_insert(node);
+                    }
+                }
+                match (path, elem.name().local_part()) {
+                    (None, "transition") => {
+                        if let Some(source) = try!(reader.get_ref(node, "source")) {
+                            self.transitions.entry(source).or_insert_with(Vec::new).push(node);
+                        }
+                    },
+                    (None, "element") if reader.dialect() == Dialect::EnterpriseArchitect && XmiIndex::in_extension(node) => {
+                        if let Some(id) = reader.get_attr(node, "idref") {
+                            self.extensions.entry(id).or_insert(node);
+                        }
+                    },
+                    _ => {}
+                }
+                if path.is_none() && elem.name().namespace_uri().map_or(false, is_profile_namespace) {
+                    for attr in elem.attributes().iter().filter(|a| a.name().local_part().starts_with("base_")) {
+                        self.stereotypes.entry(attr.value().to_string()).or_insert_with(Vec::new).push(node);
+                    }
+                }
+            }
+            // Reversed so that elements are visited in document order
+            stack.extend(node.children().into_iter().rev());
+        }
+        Ok(())
+    }
+
+    /// Whether `node` is in the `xmi:Extension/elements` of the document element
+    fn in_extension(node: Node) -> bool {
+        let elements  = node.parent();
+        let extension = elements.and_then(|x| x.parent());
+        let local_name = |x: Option<Node>| match x {
+            Some(Node::Element(elem)) => elem.name().local_part().to_string(),
+            _                         => String::new()
+        };
+        local_name(elements) == "elements" && local_name(extension) == "Extension"
+    }
+
+    /// The element whose xmi:id is `id`, wherever it is in the model. An id
+    /// like `/models/other.uml#id`, as `get_ref` gives references to other
+    /// documents, refers to an element of another document.
+    pub fn element_by_id(&self, id: &str) -> Result<Option<Node<'d>>> {
+        match id.find('#') {
+            Some(0)    => Ok(self.elements.get(&id[1..]).cloned()),
+            Some(hash) => {
+                if !self.reader.references().iter().any(|&(path, _)| path == &id[..hash]) {
+                    return Err(self.reader.unloaded_document(&id[..hash]))
+                }
+                Ok(self.elements.get(id).cloned())
+            },
+            None       => Ok(self.elements.get(id).cloned())
+        }
+    }
+
+    /// The application of the RustHsm stereotype `name` to `node`
+    pub fn stereotype(&self, node: Node, name: &str) -> Option<Node<'d>> {
+        let id = match self.reader.get_attr(node, "id") {
+            Some(x) => x,
+            None    => return None
+        };
+        self.stereotypes.get(&id).and_then(|apps| apps.iter().cloned().find(|app| match *app {
+            Node::Element(elem) => elem.name().local_part() == name,
+            _                   => false
+        }))
+    }
+
+    /// The first signal event named `name`, in the model or else in the
+    /// documents it refers to
+    pub fn signal_event(&self, name: &str) -> Option<Node<'d>> {
+        self.signals.get(name).cloned()
+    }
+
+    /// Transitions whose source is the vertex `id`
+    pub fn transitions_from(&self, id: &str) -> Result<Vec<Node<'d>>> {
+        Ok(self.transitions.get(id).cloned().unwrap_or(Vec::new()))
+    }
+
+    /// The event element a `trigger` refers to. Enterprise Architect writes the
+    /// trigger itself elsewhere and only refers to it with `xmi:idref`, and the
+    /// event may live in any package of the model.
+    pub fn trigger_event(&self, trig_node: Node<'d>) -> Result<Option<Node<'d>>> {
+        let reader = self.reader;
+        let trig_node = match reader.get_attr(trig_node, "idref") {
+            Some(trig_id) => match try!(self.element_by_id(&trig_id)) {
+                Some(x) => x,
+                None    => return Err(reader.error(trig_node, format!("trigger reference to unknown trigger {}", trig_id)))
+            },
+            None          => trig_node
+        };
+        match try!(reader.get_ref(trig_node, "event")) {
+            Some(evt_id) => match try!(self.element_by_id(&evt_id)) {
+                Some(x) => Ok(Some(x)),
+                None    => Err(reader.error(trig_node, format!("trigger refers to unknown event {}", evt_id)))
+            },
+            None         => Ok(None)
+        }
+    }
+
+    /// The code of the `kind` (entry, exit, doActivity, effect) behavior of `owner`.
+    /// MagicDraw may only refer to an opaque behavior kept in `ownedBehavior`.
+    /// Enterprise Architect keeps state behaviors as stereotyped operations of the
+    /// state in its `xmi:Extension` block.
+    pub fn behavior(&self, owner: Node<'d>, kind: &str) -> Result<Option<String>> {
+        let reader = self.reader;
+        if let Some(beh) = get_node_opt!(reader, owner, kind) {
+            if let Some(body) = try!(reader.body(beh)) {
+                return Ok(Some(body))
+            }
+            if let Some(beh_id) = reader.get_attr(beh, "idref") {
+                match try!(self.element_by_id(&beh_id)) {
+                    Some(beh) => return reader.body(beh),
+                    None      => return Err(reader.error(beh, format!("reference to unknown behavior {}", beh_id)))
+                }
+            }
+        }
+        if let Some(element) = reader.get_attr(owner, "id").and_then(|id| self.extensions.get(&id).cloned()) {
+            let stereotype = if kind == "doActivity" { "do" } else { kind };
+            let op = get_node_opt!(reader, element, &format!(
+                "operations/operation[stereotype/@stereotype='{}']", stereotype
+            ));
+            if let Some(op) = op {
+                return Ok(match get_node_opt!(reader, op, "behaviour/@behaviour") {
+                    Some(code) if !code.string_value().trim().is_empty() => Some(reader.decode(&code.string_value())),
+                    _                                                    => reader.get_attr(op, "name").map(|x| format!("{}()", x))
+                })
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<'d> Deref for XmiIndex<'d> {
+    type Target = XmiModel<'d>;
+
+    fn deref(&self) -> &XmiModel<'d> {
+        self.reader
+    }
+}
@@ -23,17 +23,19 @@
  */
 macro_rules! get_ns {
     ($slf:ident, $e:expr) => {
-        if let ::sxd_xpath::Value::Nodeset(nodeset) = $slf.evaluate_root($e) {
-            nodeset
-        } else {
-            panic!("get_ns macro: evaluate_root({}) did not return a Value::Nodeset", $e);
+        match try!($slf.evaluate_root($e)) {
+            ::sxd_xpath::Value::Nodeset(nodeset) => nodeset,
+            _ => return Err(::error::HsmGenError::xpath(
+                format!("get_ns macro: evaluate_root({}) did not return a Value::Nodeset", $e)
+            ))
         }
     };
     ($slf:ident, $n:ident, $e:expr) => {
-        if let ::sxd_xpath::Value::Nodeset(nodeset) = $slf.evaluate($n, $e) {
-            nodeset
-        } else {
-            panic!("get_ns macro: evaluate({:?}, {}) did not return a Value::Nodeset", $n, $e);
+        match try!($slf.evaluate($n, $e)) {
+            ::sxd_xpath::Value::Nodeset(nodeset) => nodeset,
+            _ => return Err($slf.error($n,
+                format!("get_ns macro: evaluate({}) did not return a Value::Nodeset", $e)
+            ))
         }
     }
 }
@@ -44,7 +46,9 @@ macro_rules! get_node {
         if ns.size() == 1 {
             ns.iter().next().unwrap()
         } else {
-            panic!("get_node macro: required nodeset length 1, but got {} with expr {}", ns.size(), $e)
+            return Err(::error::HsmGenError::model(
+                format!("get_node macro: required nodeset length 1, but got {} with expr {}", ns.size(), $e)
+            ))
         }
     }};
     ($slf:ident, $n:ident, $e:expr) => {{
@@ -52,7 +56,9 @@ macro_rules! get_node {
         if ns.size() == 1 {
             ns.iter().next().unwrap()
         } else {
-            panic!("get_node macro: required nodeset length 1, but got {} under node {:?} with expr {}", ns.size(), $n, $e)
+            return Err($slf.error($n,
+                format!("get_node macro: required nodeset length 1, but got {} with expr {}", ns.size(), $e)
+            ))
         }
     }}
 }
@@ -63,7 +69,9 @@ macro_rules! get_node_opt {
         match ns.size() {
             0 => None,
             1 => Some(ns.iter().next().unwrap()),
-            _ => panic!("get_node_opt macro: required nodeset length 0 or 1, but got {} with expr {}", ns.size(), $e)
+            _ => return Err(::error::HsmGenError::model(
+                format!("get_node_opt macro: required nodeset length 0 or 1, but got {} with expr {}", ns.size(), $e)
+            ))
         }
     }};
     ($slf:ident, $n:ident, $e:expr) => {{
@@ -71,7 +79,9 @@ macro_rules! get_node_opt {
         match ns.size() {
             0 => None,
             1 => Some(ns.iter().next().unwrap()),
-            _ => panic!("get_node_opt macro: required nodeset length 0 or 1, but got {} under node {:?} with expr {}", ns.size(), $n, $e)
+            _ => return Err($slf.error($n,
+                format!("get_node_opt macro: required nodeset length 0 or 1, but got {} with expr {}", ns.size(), $e)
+            ))
         }
     }}
 }
@@ -82,7 +92,9 @@ macro_rules! get_attr_val_str {
         if let ::sxd_xpath::nodeset::Node::Attribute(x) = $i {
             x.value().to_string()
         } else {
-            panic!("get_attr_val_str macro: {:?} is not Node::Attribute", $i);
+            return Err(::error::HsmGenError::model(
+                format!("get_attr_val_str macro: {:?} is not Node::Attribute", $i)
+            ))
         }
     }
 }
@@ -92,7 +104,9 @@ macro_rules! get_attrs {
         if let ::sxd_xpath::nodeset::Node::Element(x) = $i {
             x.attributes()
         } else {
-            panic!("get_attrs macro: {:?} is not Node::Element", $i);
+            return Err(::error::HsmGenError::model(
+                format!("get_attrs macro: {:?} is not Node::Element", $i)
+            ))
         }
     }
 }
@@ -24,6 +24,10 @@
 #[macro_use]
 mod macros;
 mod reader;
-mod inner;
+mod index;
+mod namespaces;
+mod dialect;
 
-pub use self::reader::XmiReader;
+pub use self::reader::{XmiReader, XmiModel};
+pub use self::index::XmiIndex;
+pub use self::dialect::Dialect;
@@ -0,0 +1,51 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+/// Namespace URIs used by the XMI exporters we know of. The version part of
+/// the URI changes between releases, so only the stable prefix is compared.
+const XMI_NAMESPACES: &'static [&'static str] = &[
+    "http://www.omg.org/XMI",
+    "http://www.omg.org/spec/XMI/",
+    "http://schema.omg.org/spec/XMI/",
+];
+
+const UML_NAMESPACES: &'static [&'static str] = &[
+    "http://www.eclipse.org/uml2/",
+    "http://www.omg.org/spec/UML/",
+    "http://schema.omg.org/spec/UML/",
+    "org.omg.xmi.namespace.UML",
+];
+
+pub fn is_xmi_namespace(uri: &str) -> bool {
+    XMI_NAMESPACES.iter().any(|x| uri.starts_with(x))
+}
+
+pub fn is_uml_namespace(uri: &str) -> bool {
+    UML_NAMESPACES.iter().any(|x| uri.starts_with(x))
+}
+
+/// Namespace of the applications of the RustHsm profile stereotypes. Papyrus
+/// names it like `http:///schemas/RustHsm/_id/0` when the profile is defined.
+pub fn is_profile_namespace(uri: &str) -> bool {
+    uri.contains("/RustHsm/") || uri.ends_with("/RustHsm")
+}
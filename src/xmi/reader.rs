@@ -23,81 +23,429 @@
  */
 use std::io::prelude::*;
 use std::fs::File;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
 use std::collections::HashMap;
 
 use sxd_document::Package;
 use sxd_document::writer::format_document;
-use sxd_document::parser::Parser;
-use sxd_xpath::{Value,Functions,Variables,Namespaces,Factory,EvaluationContext,Expression};
-use sxd_xpath::function::register_core_functions;
+use sxd_xpath::{Value,Factory,Context};
 use sxd_xpath::nodeset::Node;
 
-use ::ir::{State, Subvertex};
-use super::inner::InnerXmiReader;
+use ::error::{HsmGenError, Result, Diagnostics};
+use ::location::{SourceLocation, index_xml_ids, read_source};
+use ::xml::{decode_entities, parse_document};
+use ::ir::{State, Subvertex, MachineHints, ModelReader, resolve_states};
+use super::namespaces::{is_xmi_namespace, is_uml_namespace};
+use super::dialect::Dialect;
+use super::index::XmiIndex;
 
 
-pub struct XmiReader<'a, 'd> {
-    inner       : Box<InnerXmiReader<'d>>,
-    functions   : Functions,
-    variables   : Variables<'a>,
-    namespaces  : Namespaces,
+/// A loaded XMI model.
+///
+/// The reader keeps the sources of the model and of the documents it refers
+/// to, so it holds no nodes and can be shared between threads. Its reads parse
+/// them for each call, `model` parses them once for several reads on one thread.
+pub struct XmiReader {
+    file        : String,
+    /// Canonical path of the model, None when it was not read from a file
+    path        : Option<PathBuf>,
+    source      : String,
+    locations   : HashMap<String, SourceLocation>,
+    /// The documents the model refers to by their canonical path
+    documents   : HashMap<String, ExternalDocument>,
+    search_path : Vec<PathBuf>,
+    diagnostics : Diagnostics,
+    dialect     : Dialect,
+}
+
+/// A document the model refers to
+struct ExternalDocument {
+    file      : String,
+    source    : String,
+    locations : HashMap<String, SourceLocation>,
+}
+
+impl XmiReader {
+    /// A reader without a model
+    pub fn new() -> Self {
+        XmiReader {
+            file        : String::new(),
+            path        : None,
+            source      : String::new(),
+            locations   : HashMap::new(),
+            documents   : HashMap::new(),
+            search_path : Vec::new(),
+            diagnostics : Diagnostics::new(),
+            dialect     : Dialect::Generic,
+        }
+    }
+
+    pub fn from_file(file: &str) -> Result<Self> {
+        let model_path = XmiReader::model_path(file);
+        let file = model_path.as_str();
+        let s = try!(read_source(file));
+        XmiReader::from_source(file, &s)
+    }
+
+    /// Reads a model held in memory. Documents it refers to are looked up
+    /// relative to the working directory.
+    pub fn from_str(s: &str) -> Result<Self> {
+        XmiReader::from_source("<string>", s)
+    }
+
+    /// Reads a model from `reader`, for example stdin
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self> {
+        let mut s = String::new();
+        try!(reader.read_to_string(&mut s).map_err(|e|
+            HsmGenError::io(format!("could not read model: {}", e))
+        ));
+        XmiReader::from_source("<input>", &s)
+    }
+
+    /// Reads the model `s`, `file` is the name used in locations and for
+    /// finding the documents it refers to
+    fn from_source(file: &str, s: &str) -> Result<Self> {
+        let mut xr = XmiReader {
+            file      : file.to_string(),
+            path      : Path::new(file).canonicalize().ok(),
+            source    : s.to_string(),
+            locations : index_xml_ids(file, s),
+            .. XmiReader::new()
+        };
+        let package = try!(XmiReader::parse(file, s));
+        xr.dialect = Dialect::detect(&package);
+        info!("Reading {} as {:?} XMI", file, xr.dialect);
+        try!(xr.load_references(vec!((None, package))));
+        Ok(xr)
+    }
+
+    fn parse(file: &str, s: &str) -> Result<Package> {
+        parse_document(file, s)
+    }
+
+    /// Parses the model, a reader made with `new` gives an empty document
+    fn parse_model(&self) -> Result<Package> {
+        if self.source.is_empty() {
+            Ok(Package::new())
+        } else {
+            XmiReader::parse(&self.file, &self.source)
+        }
+    }
+
+    /// Parses the model and the documents it refers to, for reading it
+    /// several times on this thread
+    pub fn model<'r>(&'r self) -> Result<XmiModel<'r>> {
+        XmiModel::new(self)
+    }
+
+    /// Adds a directory to look for documents referred to with `href="other.uml#id"`
+    /// in, after the directory of the document referring to them, and loads
+    /// those found there.
+    pub fn add_search_path<P: AsRef<Path>>(&mut self, dir: P) -> Result<()> {
+        self.search_path.push(dir.as_ref().to_path_buf());
+        // References that were not found before may be found now
+        let mut documents = vec!((None, try!(self.parse_model())));
+        for (key, doc) in self.documents.iter() {
+            documents.push((Some(key.clone()), try!(XmiReader::parse(&doc.file, &doc.source))));
+        }
+        self.load_references(documents)
+    }
+
+    /// Loads the documents that `documents`, parsed and keyed like `documents`
+    /// of the reader, refer to and that are not loaded yet but can be found.
+    /// Then those they refer to in turn, each document is parsed once.
+    fn load_references(&mut self, mut documents: Vec<(Option<String>, Package)>) -> Result<()> {
+        while !documents.is_empty() {
+            let mut loaded = Vec::new();
+            for (key, package) in documents {
+                let dir = self.document_dir(key.as_ref().map(|x| x.as_str()));
+                for href in try!(document_references(&package)) {
+                    // Missing documents are reported when an element of them is needed
+                    let key = match self.find_document(&href, &dir).ok().and_then(|path| self.document_key(path)) {
+                        Some(key) => key,
+                        None      => continue
+                    };
+                    if self.documents.contains_key(&key) {
+                        continue
+                    }
+                    info!("Reading {} referred to from {}", key, self.file);
+                    let s = try!(read_source(&key));
+                    let package = try!(XmiReader::parse(&key, &s));
+                    self.documents.insert(key.clone(), ExternalDocument {
+                        locations : index_xml_ids(&key, &s),
+                        file      : key.clone(),
+                        source    : s,
+                    });
+                    loaded.push((Some(key), package));
+                }
+            }
+            documents = loaded;
+        }
+        Ok(())
+    }
+
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    /// Papyrus keeps diagrams in .di and .notation files next to the .uml
+    /// model, given one of those the model itself is read instead.
+    fn model_path(file: &str) -> String {
+        let path = Path::new(file);
+        match path.extension().and_then(|x| x.to_str()) {
+            Some("di") | Some("notation") => {
+                let model = path.with_extension("uml");
+                info!("{} is a Papyrus sidecar file, reading {}", file, model.display());
+                model.to_string_lossy().into_owned()
+            },
+            _ => file.to_string()
+        }
+    }
+
+    /// The qualified names of the state machines in the model, like
+    /// `Model::Controller::Door`, sorted
+    pub fn state_machines(&self) -> Result<Vec<String>> {
+        try!(self.model()).state_machines()
+    }
+
+    /// Reads the state machine named `name`, either its qualified name or its
+    /// own name when that is unique in the model.
+    pub fn read_machine(&self, name: &str) -> Result<HashMap<String, State>> {
+        self.read(|model| model.read_machine(name))
+    }
+
+    /// The generator hints the `RustHsm` profile gives the state machine named
+    /// `name`, or the only one of the model when no name is given
+    pub fn machine_hints(&self, name: Option<&str>) -> Result<MachineHints> {
+        try!(self.model()).machine_hints(name)
+    }
+
+    /// Runs `read` on the model parsed for it, the diagnostics of the reader
+    /// are then those of this read
+    fn read<T, F>(&self, read: F) -> Result<T>
+        where F: FnOnce(&XmiModel) -> Result<T> {
+        let model = try!(self.model());
+        let result = read(&model);
+        self.diagnostics.replace(model.diagnostics());
+        result
+    }
+
+    /// The error for an element of the document `href`, which was not loaded
+    pub fn unloaded_document(&self, href: &str) -> HsmGenError {
+        match self.find_document(href, &self.document_dir(None)) {
+            Ok(path) => HsmGenError::io(format!("{} was not there when the model was read", path.display())),
+            Err(e)   => e,
+        }
+    }
+
+    /// The canonical path of the document `href` refers to. A relative path is
+    /// looked up in `dir`, the directory of the referring document, and then in
+    /// the search path.
+    fn find_document(&self, href: &str, dir: &Path) -> Result<PathBuf> {
+        let href = Path::new(href);
+        let canonical = |path: &Path| path.canonicalize().map_err(|e|
+            HsmGenError::io(format!("could not resolve {}: {}", path.display(), e))
+        );
+        if href.is_absolute() {
+            if href.is_file() {
+                return canonical(href)
+            }
+            return Err(HsmGenError::io(format!("could not find {}", href.display())))
+        }
+        let mut tried = Vec::new();
+        for dir in Some(dir.to_path_buf()).into_iter().chain(self.search_path.iter().cloned()) {
+            let path = dir.join(href);
+            if path.is_file() {
+                return canonical(&path)
+            }
+            tried.push(path.to_string_lossy().into_owned());
+        }
+        Err(HsmGenError::io(format!("could not find {}, tried {}", href.display(), tried.join(", "))))
+    }
+
+    /// The directory of the document with the canonical path `key`, or of the
+    /// model itself
+    fn document_dir(&self, key: Option<&str>) -> PathBuf {
+        let file = key.unwrap_or(&self.file);
+        Path::new(file).parent().map(|x| x.to_path_buf()).unwrap_or(PathBuf::new())
+    }
+
+    /// The key `path` is indexed under, None for the model itself
+    fn document_key(&self, path: PathBuf) -> Option<String> {
+        if self.path.as_ref() == Some(&path) {
+            return None
+        }
+        Some(path.to_string_lossy().into_owned())
+    }
+}
+
+
+/// The parsed documents of an `XmiReader`. The nodes of its methods borrow
+/// it, everything else is done by the reader it derefs to. Its diagnostics are
+/// those of the reads made through it.
+pub struct XmiModel<'r> {
+    reader      : &'r XmiReader,
+    package     : Package,
+    external    : HashMap<String, Package>,
+    context     : Context<'static>,
     factory     : Factory,
+    diagnostics : Diagnostics,
 }
 
-impl<'a:'d, 'd> XmiReader<'a, 'd> {
-    fn _new(pkg: Package) -> Self {
-        let mut fns = HashMap::new();
-        register_core_functions(&mut fns);
-        let xr = XmiReader {
-            inner       : Box::new(InnerXmiReader::new(pkg)),
-            functions   : fns,
-            variables   : HashMap::new(),
-            namespaces  : HashMap::new(),
+impl<'r> XmiModel<'r> {
+    /// Parses the model of `reader` and the documents it refers to, reads
+    /// through it collect all problems when the reader does
+    pub fn new(reader: &'r XmiReader) -> Result<Self> {
+        let package = try!(reader.parse_model());
+        let mut external = HashMap::new();
+        for (key, doc) in reader.documents.iter() {
+            external.insert(key.clone(), try!(XmiReader::parse(&doc.file, &doc.source)));
+        }
+        let diagnostics = Diagnostics::new();
+        diagnostics.set_collect_all(reader.diagnostics.collect_all());
+        Ok(XmiModel {
+            reader      : reader,
+            context     : XmiModel::declared_namespaces(&package),
+            package     : package,
+            external    : external,
             factory     : Factory::new(),
-        };
-        xr.inner.init();
-        xr
+            diagnostics : diagnostics,
+        })
     }
 
-    pub fn new() -> Self {
-        XmiReader::_new(Package::new())
+    /// The qualified names of the state machines in the model, like
+    /// `Model::Controller::Door`, sorted
+    pub fn state_machines(&self) -> Result<Vec<String>> {
+        let machines = try!(self.state_machine_nodes());
+        Ok(machines.into_iter().map(|(name, _)| name).collect())
     }
 
-    pub fn from_file(file: &str) -> Self {
-        let mut f = File::open(file).expect("No such file");
-        let mut s = String::new();
-        f.read_to_string(&mut s).expect("Could not read to string");
-        let parser = Parser::new();
-        let parseres = parser.parse(&s);
-        // println!("{:#?}", parseres);
-        XmiReader::_new(parseres.ok().expect("Could not parse"))
+    /// Reads the state machine named `name`, either its qualified name or its
+    /// own name when that is unique in the model.
+    pub fn read_machine(&self, name: &str) -> Result<HashMap<String, State>> {
+        let machine = try!(self.machine_node(Some(name)));
+        self.read_machine_node(machine)
     }
 
-    pub fn evaluate_root(&'a self, xpath: &str) -> Value<'d>{
-        self.evaluate(self.inner.doc().root(), xpath)
+    /// The generator hints the `RustHsm` profile gives the state machine named
+    /// `name`, or the only one of the model when no name is given
+    pub fn machine_hints(&self, name: Option<&str>) -> Result<MachineHints> {
+        let machine = try!(self.machine_node(name));
+        let index = try!(XmiIndex::new(self));
+        MachineHints::from_xml(&index, machine)
     }
 
-    pub fn evaluate<N>(&'a self, node: N, xpath: &str) -> Value<'d>
+    /// Records `err` and returns Ok in collect-all mode, returns it otherwise
+    pub fn recover(&self, err: HsmGenError) -> Result<()> {
+        self.diagnostics.recover(err)
+    }
+
+    pub fn warn(&self, err: HsmGenError) {
+        self.diagnostics.warn(err)
+    }
+
+    /// Binds the `xmi` and `uml` prefixes for XPath expressions to whatever
+    /// namespace URIs the document element declares for XMI and UML.
+    fn declared_namespaces(package: &Package) -> Context<'static> {
+        let mut context = Context::new();
+        for child in package.as_document().root().children() {
+            if let Some(elem) = child.element() {
+                for ns in elem.namespaces_in_scope() {
+                    if is_xmi_namespace(ns.uri()) {
+                        context.set_namespace("xmi", ns.uri());
+                    } else if is_uml_namespace(ns.uri()) {
+                        context.set_namespace("uml", ns.uri());
+                    }
+                }
+            }
+        }
+        context
+    }
+
+    pub fn evaluate_root<'d>(&'d self, xpath: &str) -> Result<Value<'d>> {
+        self.evaluate(self.package.as_document().root(), xpath)
+    }
+
+    pub fn evaluate<'d, N>(&'d self, node: N, xpath: &str) -> Result<Value<'d>>
         where N: Into<Node<'d>> {
-        let context = EvaluationContext::new(
-            node,
-            &self.functions,
-            &self.variables,
-            &self.namespaces,
-        );
-        let xpath = self.factory.build(xpath).unwrap().unwrap();
-        xpath.evaluate(&context).ok().unwrap()
+        evaluate(&self.factory, &self.context, node, xpath)
     }
 
-    pub fn read_states(&'a self) -> HashMap<String, State> {
+    fn state_machine_nodes<'d>(&'d self) -> Result<Vec<(String, Node<'d>)>> {
+        let mut machines = Vec::new();
+        for node in get_ns!(self, "//*[@*[local-name()='type']]").iter() {
+            if self.get_type(node).as_ref().map(|x| x.as_str()) == Some("uml:StateMachine") {
+                machines.push((self.qualified_name(node), node));
+            }
+        }
+        // Node sets are unordered
+        machines.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(machines)
+    }
+
+    /// The names of `node` and of the named elements containing it, joined with `::`
+    pub fn qualified_name(&self, node: Node) -> String {
+        let mut names = Vec::new();
+        let mut cur = Some(node);
+        while let Some(n) = cur {
+            if let Some(name) = self.get_attr(n, "name") {
+                names.push(name);
+            }
+            cur = n.parent();
+        }
+        names.reverse();
+        names.join("::")
+    }
+
+    /// The state machine named `name`, or the only one of the model. A model
+    /// without state machine elements is read as a whole.
+    fn machine_node<'d>(&'d self, name: Option<&str>) -> Result<Node<'d>> {
+        let machines = try!(self.state_machine_nodes());
+        let names = |list: Vec<&String>| list.iter().map(|x| x.as_str()).collect::<Vec<_>>().join(", ");
+        let name = match name {
+            Some(name) => name,
+            None       => return match machines.len() {
+                0 => Ok(self.root()),
+                1 => Ok(machines[0].1),
+                _ => Err(HsmGenError::model(format!(
+                    "model has {} state machines ({}), pick one of them",
+                    machines.len(), names(machines.iter().map(|x| &x.0).collect())
+                )))
+            }
+        };
+        if let Some(&(_, node)) = machines.iter().find(|&&(ref qname, _)| qname == name) {
+            return Ok(node)
+        }
+        let matching = machines.iter()
+            .filter(|&&(ref qname, _)| qname.rsplit("::").next() == Some(name))
+            .collect::<Vec<_>>();
+        match matching.len() {
+            1 => Ok(matching[0].1),
+            0 => Err(HsmGenError::model(format!(
+                "no state machine {:?} in the model, it has: {}", name, names(machines.iter().map(|x| &x.0).collect())
+            ))),
+            _ => Err(HsmGenError::model(format!(
+                "{:?} matches several state machines ({}), use the qualified name", name, names(matching.iter().map(|x| &x.0).collect())
+            )))
+        }
+    }
+
+    /// Reads the states of the state machine `machine`. Its vertices are looked
+    /// up below it, so those of other machines in the model are left out.
+    fn read_machine_node<'d>(&'d self, machine: Node<'d>) -> Result<HashMap<String, State>> {
         let mut sm = HashMap::new();
         let mut vm = HashMap::new();
 
-        let subvertexes = get_ns!(self, "//subvertex").iter()
-                                .map(|x| Subvertex::from_xml(self, x))
-                                .filter(|x| x.is_some())
-                                .map(|x| x.unwrap())
-                                .collect::<Vec<Subvertex>>();
+        let index = try!(XmiIndex::new(self));
+        let mut subvertexes = Vec::new();
+        for node in get_ns!(self, machine, ".//subvertex").iter() {
+            match Subvertex::from_xml(&index, node) {
+                Ok(Some(subvertex)) => subvertexes.push(subvertex),
+                Ok(None)            => {},
+                Err(e)              => try!(self.recover(e)),
+            }
+        }
         // debug!("{:#?}", subvertexes);
         subvertexes.into_iter().map(|subvertex|
             match subvertex {
@@ -109,55 +457,344 @@ impl<'a:'d, 'd> XmiReader<'a, 'd> {
             }
         ).count();
         // debug!("{:#?}", sm);
-        // Convert transitions to condactions
-        for key in sm.keys().map(|x| x.to_string()).collect::<Vec<String>>().iter() {
-            let mut state = sm.get(key).unwrap().clone();
-            while let Some(trans) = state.transitions.pop() {
-                state.add_action(trans, &sm, &vm);
+        resolve_states(sm, vm, &self.diagnostics)
+    }
+
+    /// The document node of the model
+    pub fn root<'d>(&'d self) -> Node<'d> {
+        self.package.as_document().root().into()
+    }
+
+    /// The document nodes of the documents the model refers to, by their
+    /// canonical path
+    pub fn references<'d>(&'d self) -> Vec<(&'d str, Node<'d>)> {
+        self.external.iter().map(|(key, pkg)| (key.as_str(), pkg.as_document().root().into())).collect()
+    }
+
+    /// The canonical path of the document `node` is in, None for the model itself
+    fn document_of(&self, node: Node) -> Option<&str> {
+        let mut root = node;
+        while let Some(parent) = root.parent() {
+            root = parent;
+        }
+        self.external.iter()
+            .find(|&(_, pkg)| Node::from(pkg.as_document().root()) == root)
+            .map(|(key, _)| key.as_str())
+    }
+
+    /// The id of the element `node` refers to with `name`. Most exporters write
+    /// the id as an attribute, MagicDraw as a child element with `xmi:idref` and
+    /// EMF a reference to another document as a child element with an `href`.
+    ///
+    /// Elements of other documents are given as `<canonical path>#id`, which is
+    /// how `XmiIndex` keeps them, also when `node` is itself in such a document.
+    pub fn get_ref<'d>(&'d self, node: Node<'d>, name: &str) -> Result<Option<String>> {
+        if let Some(id) = self.get_attr(node, name) {
+            return Ok(Some(self.resolve_ref(node, id)))
+        }
+        match get_node_opt!(self, node, name) {
+            Some(child) => Ok(self.get_attr(child, "idref").or_else(|| self.get_attr(child, "href"))
+                                  .map(|id| self.resolve_ref(node, id))),
+            None        => Ok(None)
+        }
+    }
+
+    /// Resolves the reference `value` made from `node`. A path before the `#`
+    /// is relative to the document of `node`, a plain id is in that document.
+    /// Documents that cannot be found, like `pathmap://` libraries, are left
+    /// as they are.
+    fn resolve_ref(&self, node: Node, value: String) -> String {
+        let doc = self.document_of(node);
+        let (href, id) = match value.find('#') {
+            Some(hash) => (value[..hash].to_string(), value[hash + 1..].to_string()),
+            None       => (String::new(), value.clone())
+        };
+        let key = if href.is_empty() {
+            doc.map(|x| x.to_string())
+        } else {
+            match self.find_document(&href, &self.document_dir(doc)) {
+                Ok(path) => self.document_key(path),
+                Err(_)   => return value
             }
-            sm.insert(key.to_string(), state);
+        };
+        match key {
+            Some(key) => format!("{}#{}", key, id),
+            None      => id
         }
-        // debug!("{:#?}", sm);
-        // Replace hashmap keys with state names
-        for key in sm.keys().map(|x| x.to_string()).collect::<Vec<String>>().iter() {
-            let state = sm.remove(key).unwrap();
-            sm.insert(state.name.clone(), state);
+    }
+
+    pub fn req_ref<'d>(&'d self, node: Node<'d>, name: &str) -> Result<String> {
+        match try!(self.get_ref(node, name)) {
+            Some(id) => Ok(id),
+            None     => Err(self.error(node, format!("missing reference {:?}", name)))
         }
-        // debug!("{:#?}", sm);
-        sm
     }
 
-    pub fn parent_state_node(&'a self, node: Node<'a>) -> Option<Node<'a>> {
-        let gp_node = node.parent().unwrap().parent().unwrap();
-        if let Node::Element(gp_elem) = gp_node {
-            if gp_elem.name().local_part() == "subvertex" {
-                return Some(gp_node)
+    /// The code of an opaque behavior or expression. It is usually a `body`
+    /// child element, Enterprise Architect writes it as an attribute. Of several
+    /// bodies the one whose `language` is Rust is taken, the first one with a
+    /// warning when there is none.
+    /// Enterprise Architect escapes code twice, its bodies are decoded once more.
+    pub fn body<'d>(&'d self, node: Node<'d>) -> Result<Option<String>> {
+        let bodies    = self.child_texts(node, "body");
+        let languages = self.child_texts(node, "language");
+        if bodies.is_empty() {
+            return Ok(self.get_attr(node, "body").map(|x| self.decode(&x)))
+        }
+        let rust = languages.iter().position(|x| x.trim().to_lowercase() == "rust");
+        let fallback = match rust {
+            Some(i) if i < bodies.len()       => return Ok(Some(self.decode(&bodies[i]))),
+            Some(_)                           => format!(
+                "{} languages for {} bodies, no body for the Rust language, using the first one", languages.len(), bodies.len()
+            ),
+            None if !languages.is_empty()     => format!("no Rust body, using the {} one", languages[0].trim()),
+            None if bodies.len() > 1          => format!("{} bodies without a language, using the first one", bodies.len()),
+            None                              => return Ok(Some(self.decode(&bodies[0]))),
+        };
+        self.warn(self.error(node, fallback));
+        Ok(Some(self.decode(&bodies[0])))
+    }
+
+    /// `text` decoded a second time when the dialect escapes code twice
+    pub fn decode(&self, text: &str) -> String {
+        match self.dialect() {
+            Dialect::EnterpriseArchitect => decode_entities(text),
+            _                            => text.to_string()
+        }
+    }
+
+    /// The texts of the child elements `name` of `node`, in document order
+    fn child_texts(&self, node: Node, name: &str) -> Vec<String> {
+        node.children().into_iter().filter(|x| match *x {
+            Node::Element(elem) => elem.name().local_part() == name,
+            _                   => false
+        }).map(|x| x.string_value()).collect()
+    }
+
+    /// The bodies of the `ownedComment`s of `node`, separated by blank lines
+    pub fn comments<'d>(&'d self, node: Node<'d>) -> Result<Option<String>> {
+        let mut bodies = Vec::new();
+        for comment in get_ns!(self, node, "ownedComment").iter() {
+            if let Some(body) = try!(self.body(comment)) {
+                if !body.trim().is_empty() {
+                    bodies.push(body.trim().to_string());
+                }
+            }
+        }
+        Ok(if bodies.is_empty() { None } else { Some(bodies.join("\n\n")) })
+    }
+
+    /// The values of tag `tag` of a stereotype application. A single value is
+    /// an attribute, EMF writes several values as child elements.
+    pub fn tag_values<'d>(&'d self, app: Node<'d>, tag: &str) -> Result<Vec<String>> {
+        if let Some(value) = self.get_attr(app, tag) {
+            return Ok(vec!(value))
+        }
+        Ok(get_ns!(self, app, tag).iter().map(|x| x.string_value()).collect())
+    }
+
+    /// Whether `node` is an initial pseudostate. The kind defaults to initial and
+    /// most exporters leave it out, Enterprise Architect writes it explicitly.
+    pub fn is_initial(&self, node: Node) -> bool {
+        self.get_type(node).as_ref().map(|x| x.as_str()) == Some("uml:Pseudostate")
+            && self.get_attr(node, "kind").as_ref().map_or(true, |x| x == "initial")
+    }
+
+    pub fn parent_state_node<'d>(&self, node: Node<'d>) -> Option<Node<'d>> {
+        if let Some(gp_node) = node.parent().and_then(|p| p.parent()) {
+            if let Node::Element(gp_elem) = gp_node {
+                if gp_elem.name().local_part() == "subvertex" {
+                    return Some(gp_node)
+                }
             }
         }
         None
     }
 
     pub fn get_attr(&self, node: Node, attr: &str) -> Option<String> {
-        for a in get_attrs!(node).iter() {
-            if a.name().local_part() == attr {
-                return Some(a.value().to_string())
+        if let Node::Element(elem) = node {
+            for a in elem.attributes().iter() {
+                if a.name().local_part() == attr {
+                    return Some(a.value().to_string())
+                }
             }
         }
         None
     }
 
-    pub fn print_node(&self, node: Node) {
+    /// The metaclass of `node` from its `xmi:type` attribute. The prefix of the
+    /// value is resolved through the namespaces in scope, so any prefix bound to
+    /// a UML namespace gives `uml:<Name>`. Documents that do not qualify the
+    /// attribute fall back to a plain `type`.
+    pub fn get_type(&self, node: Node) -> Option<String> {
+        let elem = match node {
+            Node::Element(elem) => elem,
+            _                   => return None
+        };
+        let attrs = elem.attributes();
+        let attr = attrs.iter()
+            .find(|a| a.name().local_part() == "type" && a.name().namespace_uri().map_or(false, is_xmi_namespace))
+            .or_else(|| attrs.iter().find(|a| a.name().local_part() == "type" && a.name().namespace_uri().is_none()));
+        attr.map(|a| {
+            let value = a.value();
+            match value.find(':') {
+                Some(i) => match elem.namespace_uri_for_prefix(&value[..i]) {
+                    Some(uri) if is_uml_namespace(uri) => format!("uml:{}", &value[i + 1..]),
+                    Some(uri)                          => format!("{{{}}}{}", uri, &value[i + 1..]),
+                    None                               => value.to_string(),
+                },
+                None    => value.to_string()
+            }
+        })
+    }
+
+    pub fn req_type(&self, node: Node) -> Result<String> {
+        self.get_type(node).ok_or_else(||
+            self.error(node, "missing attribute \"xmi:type\"")
+        )
+    }
+
+    /// Like `get_attr`, but a missing attribute is an error naming the element
+    pub fn req_attr(&self, node: Node, attr: &str) -> Result<String> {
+        self.get_attr(node, attr).ok_or_else(||
+            self.error(node, format!("missing attribute {:?}", attr))
+        )
+    }
+
+    /// The name of `node`, falling back to its xmi:id in collect-all mode
+    pub fn name_attr(&self, node: Node) -> Result<String> {
+        match self.get_attr(node, "name") {
+            Some(name) => Ok(name),
+            None       => {
+                try!(self.recover(self.error(node, "missing attribute \"name\"")));
+                self.req_attr(node, "id")
+            }
+        }
+    }
+
+    /// Where the element `node` starts in the source file of its document
+    pub fn location(&self, node: Node) -> Option<SourceLocation> {
+        let id = match self.get_attr(node, "id") {
+            Some(id) => id,
+            None     => return None
+        };
+        let locations = match self.document_of(node) {
+            Some(key) => match self.documents.get(key) {
+                Some(doc) => &doc.locations,
+                None      => return None
+            },
+            None      => &self.locations
+        };
+        locations.get(&id).cloned()
+    }
+
+    /// Builds a model error that carries the kind, xmi:id and location of `node`
+    pub fn error<S: Into<String>>(&self, node: Node, message: S) -> HsmGenError {
+        let element = match self.get_type(node) {
+            Some(typ) => Some(typ),
+            None      => match node {
+                Node::Element(elem) => Some(elem.name().local_part().to_string()),
+                _                   => None
+            }
+        };
+        HsmGenError {
+            element  : element,
+            id       : self.get_attr(node, "id"),
+            location : self.location(node),
+            .. HsmGenError::model(message)
+        }
+    }
+
+    pub fn print_node(&self, node: Node) -> Result<()> {
         println!("{:#?}", node);
         println!("{:#?}", node.children());
         for attr in get_attrs!(node) {
             println!("{:#?}={:#?}", attr.name().local_part(), attr.value());
         }
+        Ok(())
     }
 
-    pub fn print(&'a self, file: &str) {
-        let doc = self.inner.doc();
-        let mut f = File::create(file).expect("Could not create file");
+    pub fn print(&self, file: &str) -> Result<()> {
+        let doc = &self.package.as_document();
+        let mut f = try!(File::create(file).map_err(|e|
+            HsmGenError::io(format!("could not create {}: {}", file, e))
+        ));
         println!("{:?}", doc);
-        format_document(doc, &mut f).expect("Error formatting document");
+        format_document(doc, &mut f).map_err(|e|
+            HsmGenError::io(format!("could not write {}: {}", file, e))
+        )
+    }
+}
+
+impl<'r> Deref for XmiModel<'r> {
+    type Target = XmiReader;
+
+    fn deref(&self) -> &XmiReader {
+        self.reader
+    }
+}
+
+impl ModelReader for XmiReader {
+    /// Reads the only state machine of the model. A model with several state
+    /// machines is an error, `read_machine` picks one of them.
+    fn read_states(&self) -> Result<HashMap<String, State>> {
+        self.read(|model| model.read_states())
+    }
+
+    /// The diagnostics of the last read
+    fn diagnostic_log(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+}
+
+impl<'r> ModelReader for XmiModel<'r> {
+    /// Reads the only state machine of the model. A model with several state
+    /// machines is an error, `read_machine` picks one of them.
+    fn read_states(&self) -> Result<HashMap<String, State>> {
+        let machine = try!(self.machine_node(None));
+        self.read_machine_node(machine)
+    }
+
+    fn diagnostic_log(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+}
+
+/// Evaluates `xpath` at `node`, with the prefixes bound in `context`
+pub fn evaluate<'d, N>(factory: &Factory, context: &Context<'d>, node: N, xpath: &str) -> Result<Value<'d>>
+    where N: Into<Node<'d>> {
+    let xpath = match factory.build(xpath) {
+        Ok(Some(expr)) => expr,
+        Ok(None)       => return Err(HsmGenError::xpath(format!("empty xpath expression {:?}", xpath))),
+        Err(e)         => return Err(HsmGenError::xpath(format!("could not build xpath {:?}: {:?}", xpath, e))),
+    };
+    xpath.evaluate(context, node).map_err(|e|
+        HsmGenError::xpath(format!("could not evaluate xpath: {:?}", e))
+    )
+}
+
+/// The attributes that refer to elements, `href` those of other documents
+const REFERENCE_ATTRIBUTES: &'static [&'static str] = &["href", "idref", "event", "signal", "source", "target", "type", "guard"];
+
+/// The documents `package` refers to with values like `other.uml#id`, in
+/// document order. Other attributes, like code, may have a `#` as well.
+fn document_references(package: &Package) -> Result<Vec<String>> {
+    let names = REFERENCE_ATTRIBUTES.iter().map(|x| format!("local-name() = '{}'", x)).collect::<Vec<_>>().join(" or ");
+    let xpath = format!("//@*[{}][contains(string(.), '#')]", names);
+    let refs = match try!(evaluate(&Factory::new(), &Context::new(), package.as_document().root(), &xpath)) {
+        Value::Nodeset(ns) => ns,
+        _                  => return Err(HsmGenError::xpath("document_references: did not get a Nodeset"))
+    };
+    let mut hrefs = Vec::new();
+    for node in refs.document_order() {
+        let value = node.string_value();
+        let href = match value.find('#') {
+            Some(hash) if hash > 0 => value[..hash].to_string(),
+            _                      => continue
+        };
+        if !hrefs.contains(&href) {
+            hrefs.push(href);
+        }
     }
+    Ok(hrefs)
 }
@@ -0,0 +1,157 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use std::collections::HashMap;
+
+use sxd_document::Package;
+use sxd_document::dom::{Element, ChildOfElement, ParentOfChild};
+use sxd_document::parser;
+
+use ::error::{HsmGenError, Result};
+use ::location::SourceLocation;
+
+
+/// Parses the XML document `src` read from `file`
+pub fn parse_document(file: &str, src: &str) -> Result<Package> {
+    parser::parse(src).map_err(|e|
+        HsmGenError::parse(format!("could not parse {}: {}", file, e))
+                    .with_location(Some(SourceLocation::from_byte(file, src, e.location())))
+    )
+}
+
+pub fn child_elements<'d>(el: Element<'d>) -> Vec<Element<'d>> {
+    el.children().into_iter().filter_map(|x| match x {
+        ChildOfElement::Element(e) => Some(e),
+        _                          => None
+    }).collect()
+}
+
+/// The child elements of `el` with local name `name`
+pub fn children_named<'d>(el: Element<'d>, name: &str) -> Vec<Element<'d>> {
+    child_elements(el).into_iter().filter(|x| x.name().local_part() == name).collect()
+}
+
+/// Descendants of `el` with local name `name`, not looking inside the ones found
+pub fn descendants_named<'d>(el: Element<'d>, name: &str, found: &mut Vec<Element<'d>>) {
+    for child in child_elements(el) {
+        if child.name().local_part() == name {
+            found.push(child);
+        } else {
+            descendants_named(child, name, found);
+        }
+    }
+}
+
+pub fn first_child<'d>(el: Element<'d>, name: &str) -> Option<Element<'d>> {
+    child_elements(el).into_iter().find(|x| x.name().local_part() == name)
+}
+
+pub fn parent_element<'d>(el: Element<'d>) -> Option<Element<'d>> {
+    match el.parent() {
+        Some(ParentOfChild::Element(p)) => Some(p),
+        _                               => None
+    }
+}
+
+/// Where `el` or the nearest enclosing element with a location is, `locations`
+/// holds them by the value of attribute `attr`
+pub fn enclosing_location(el: Element, attr: &str, locations: &HashMap<String, SourceLocation>) -> Option<SourceLocation> {
+    let mut cur = Some(el);
+    while let Some(e) = cur {
+        if let Some(loc) = attribute(e, attr).and_then(|x| locations.get(x)) {
+            return Some(loc.clone())
+        }
+        cur = parent_element(e);
+    }
+    None
+}
+
+/// The value of the attribute of `el` with local name `name`, in any namespace
+pub fn attribute<'d>(el: Element<'d>, name: &str) -> Option<&'d str> {
+    el.attributes().into_iter().find(|x| x.name().local_part() == name).map(|x| x.value())
+}
+
+/// The text content of `el`, trimmed
+pub fn text(el: Element) -> String {
+    el.children().into_iter().filter_map(|x| match x {
+        ChildOfElement::Text(t) => Some(t.text().to_string()),
+        _                       => None
+    }).collect::<Vec<String>>().concat().trim().to_string()
+}
+
+/// Decodes the entity and character references and unwraps the CDATA sections
+/// of `text`. The parser does this once, Enterprise Architect escapes code a
+/// second time. Text of other exporters must not go through this, code like
+/// `"&lt;"` would lose its escape.
+pub fn decode_entities(text: &str) -> String {
+    const CDATA_START: &'static str = "<![CDATA[";
+    let mut out  = String::with_capacity(text.len());
+    let mut rest = text;
+    loop {
+        let amp   = rest.find('&');
+        let cdata = rest.find(CDATA_START);
+        match (amp, cdata) {
+            (_, Some(start)) if amp.map_or(true, |amp| start < amp) => {
+                out.push_str(&rest[..start]);
+                rest = &rest[start + CDATA_START.len()..];
+                let end = rest.find("]]>").unwrap_or(rest.len());
+                out.push_str(&rest[..end]);
+                rest = &rest[::std::cmp::min(end + 3, rest.len())..];
+            },
+            (Some(amp), _) => {
+                out.push_str(&rest[..amp]);
+                rest = &rest[amp..];
+                match rest.find(';').and_then(|semi| decode_reference(&rest[1..semi]).map(|c| (c, semi))) {
+                    Some((c, semi)) => {
+                        out.push(c);
+                        rest = &rest[semi + 1..];
+                    },
+                    None            => {
+                        out.push('&');
+                        rest = &rest[1..];
+                    }
+                }
+            },
+            (None, _) => {
+                out.push_str(rest);
+                return out
+            }
+        }
+    }
+}
+
+/// The character an entity or character reference (without `&` and `;`) stands for
+fn decode_reference(name: &str) -> Option<char> {
+    match name {
+        "lt"   => Some('<'),
+        "gt"   => Some('>'),
+        "amp"  => Some('&'),
+        "apos" => Some('\''),
+        "quot" => Some('"'),
+        _ if name.starts_with("#x") || name.starts_with("#X") =>
+            u32::from_str_radix(&name[2..], 16).ok().and_then(::std::char::from_u32),
+        _ if name.starts_with('#') =>
+            u32::from_str_radix(&name[1..], 10).ok().and_then(::std::char::from_u32),
+        _      => None
+    }
+}
@@ -0,0 +1,309 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+use std::collections::HashMap;
+
+use rustc_serialize::json::Json;
+
+use ::error::{HsmGenError, Result, Diagnostics};
+use ::location::{SourceLocation, index_json_keys, json_pointer, read_source};
+use ::json::parse_json;
+use ::ir::{State, Transition, Event, ModelBuilder, VertexKind, parse_duration_ms, join_code, ModelReader};
+
+
+/// Reads XState machine configs saved as JSON.
+///
+/// A state's id is its path from the machine, like `closed.locked`, and its
+/// name the last part of the path, which must be unique. Targets are siblings (`opened`,
+/// `closed.locked`), children (`.locked`) or states with an `id` (`#door.opened`).
+/// Guards and actions are their names or `type`s, taken as Rust code, and
+/// `after` delays are milliseconds.
+pub struct XStateReader {
+    file        : String,
+    json        : Json,
+    diagnostics : Diagnostics,
+    locations   : HashMap<String, SourceLocation>,
+}
+
+impl XStateReader {
+    pub fn from_file(file: &str) -> Result<Self> {
+        let s = try!(read_source(file));
+        let json = try!(parse_json(file, &s));
+        Ok(XStateReader {
+            file        : file.to_string(),
+            json        : json,
+            diagnostics : Diagnostics::new(),
+            locations   : index_json_keys(file, &s),
+        })
+    }
+
+    /// Where the member `members` of the config of the state at `path` is,
+    /// the state itself when there are none
+    fn location(&self, path: &str, members: &[&str]) -> Option<SourceLocation> {
+        let mut keys = Vec::new();
+        for key in path.split('.') {
+            keys.push("states");
+            keys.push(key);
+        }
+        keys.extend(members.iter().cloned());
+        self.locations.get(&json_pointer(&keys)).cloned()
+    }
+
+    /// An error about the state at `path`, located at its config
+    fn error<S: Into<String>>(&self, path: &str, msg: S) -> HsmGenError {
+        HsmGenError::model(msg).context("state", path).with_location(self.location(path, &[]))
+    }
+
+    fn read_state(&self, builder: &mut ModelBuilder, ids: &HashMap<String, String>, key: &str, node: &Json, parent: Option<&str>) -> Result<()> {
+        let path = match parent {
+            Some(p) => format!("{}.{}", p, key),
+            None    => key.to_string()
+        };
+        match node.find("type").and_then(|x| x.as_string()).unwrap_or("") {
+            "final"   => {
+                builder.add_vertex(&path, VertexKind::Final, parent.map(|x| x.to_string()), self.location(&path, &[]));
+                return Ok(())
+            },
+            "history" => return Err(self.error(&path, "history states are not supported")),
+            _         => {}
+        }
+        let mut state = State::new(&path, key);
+        state.parent   = parent.map(|x| x.to_string());
+        state.entry    = actions(node.find("entry"));
+        state.exit     = actions(node.find("exit"));
+        state.location = self.location(&path, &[]);
+        builder.add_state(state);
+
+        let children = node.find("states").and_then(|x| x.as_object());
+        let is_parallel = node.find("type").and_then(|x| x.as_string()) == Some("parallel");
+        let child_count = children.map_or(0, |x| x.len());
+        if is_parallel && child_count > 1 {
+            try!(self.diagnostics.recover(self.error(&path, format!("parallel state has {} regions, only one is supported", child_count))));
+        }
+        let initial = match node.find("initial").and_then(|x| x.as_string()) {
+            Some(x)                  => Some(x.to_string()),
+            // The object does not keep the order of its members, their
+            // locations give the region written first
+            None if is_parallel      => children.and_then(|x| x.keys().min_by_key(|key|
+                self.location(&format!("{}.{}", path, key), &[]).map(|x| x.byte)
+            )).map(|x| x.to_string()),
+            None if child_count > 0  => return Err(self.error(&path, "compound state without initial")),
+            None                     => None
+        };
+        if let Some(initial) = initial {
+            let initial_id = format!("{}_initial", path);
+            // The initial state is a child, written with or without the leading dot
+            let target = if initial.starts_with('.') || initial.starts_with('#') { initial } else { format!(".{}", initial) };
+            let target = try!(self.resolve(ids, &path, parent, &target));
+            let location = self.location(&path, &["initial"]).or(self.location(&path, &[]));
+            builder.add_vertex(&initial_id, VertexKind::Initial, Some(path.clone()), location.clone());
+            let mut t = Transition::new(&format!("{}_t", initial_id), &initial_id, &target);
+            t.location = location;
+            builder.add_transition(t);
+        }
+
+        if let Some(on) = node.find("on") {
+            match on.as_object() {
+                Some(on) => for (event, config) in on.iter() {
+                    let evt = match event.as_str() {
+                        "*" => Event::Any,
+                        ""  => {
+                            try!(self.diagnostics.recover(self.error(&path, "eventless transitions are not supported")));
+                            continue
+                        },
+                        x   => Event::signal(x)
+                    };
+                    if let Err(e) = self.read_transitions(builder, ids, &path, evt, config, &["on", event]) {
+                        try!(self.diagnostics.recover(e));
+                    }
+                },
+                None     => try!(self.diagnostics.recover(self.error(&path, "on must be an object of event names"))),
+            }
+        }
+        if let Some(after) = node.find("after") {
+            match after.as_object() {
+                Some(after) => for (delay, config) in after.iter() {
+                    let evt = match parse_duration_ms(delay) {
+                        Some(ms) => Event::after(key, ms, None),
+                        None     => {
+                            try!(self.diagnostics.recover(self.error(&path, format!("named delay {:?} is not supported, give it in milliseconds", delay))));
+                            continue
+                        }
+                    };
+                    if let Err(e) = self.read_transitions(builder, ids, &path, evt, config, &["after", delay]) {
+                        try!(self.diagnostics.recover(e));
+                    }
+                },
+                None        => try!(self.diagnostics.recover(self.error(&path, "after must be an object of delays"))),
+            }
+        }
+        if node.find("always").is_some() {
+            try!(self.diagnostics.recover(self.error(&path, "always transitions are not supported")));
+        }
+        if let Some(children) = children {
+            for (child_key, child) in children.iter() {
+                if let Err(e) = self.read_state(builder, ids, child_key, child, Some(&path)) {
+                    try!(self.diagnostics.recover(e));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// A transition config: a target, an object with target, guard and
+    /// actions, or an array of these tried in order. `members` lead to it
+    /// from the config of the source.
+    fn read_transitions(&self, builder: &mut ModelBuilder, ids: &HashMap<String, String>, source: &str, evt: Event, config: &Json, members: &[&str]) -> Result<()> {
+        let parent = source.rfind('.').map(|x| &source[..x]);
+        let configs = match *config {
+            Json::Array(ref arr) => arr.iter().collect::<Vec<&Json>>(),
+            _                    => vec![config]
+        };
+        let is_array = config.is_array();
+        for (i, config) in configs.into_iter().enumerate() {
+            let index = i.to_string();
+            let mut members = members.to_vec();
+            if is_array {
+                members.push(&index);
+            }
+            let location = self.location(source, &members);
+            let (target, guard, effect) = match *config {
+                Json::String(ref target) => (Some(target.as_str()), None, None),
+                Json::Object(_)          => {
+                    let target = match config.find("target") {
+                        Some(&Json::String(ref x))                 => Some(x.as_str()),
+                        Some(&Json::Array(ref x)) if x.len() == 1  => x[0].as_string(),
+                        Some(&Json::Null) | None                   => None,
+                        Some(_)                                    => return Err(self.error(source, "transition with several targets is not supported").with_location(location.clone()))
+                    };
+                    let guard = config.find("cond").or(config.find("guard")).and_then(name);
+                    (target, guard, actions(config.find("actions")))
+                },
+                _                        => return Err(self.error(source, "transition must be a target or an object").with_location(location.clone()))
+            };
+            match target {
+                Some(target) => {
+                    let target = try!(self.resolve(ids, source, parent, target).map_err(|e| e.with_location(location.clone())));
+                    let mut t = Transition::new(&format!("{}_{}_{}", source, event_name(&evt), i), source, &target);
+                    t.guard    = guard;
+                    t.effect   = effect;
+                    t.trigger  = Some(evt.clone());
+                    t.location = location;
+                    builder.add_transition(t);
+                },
+                // A transition without target runs its actions and stays in the state
+                None         => builder.add_internal(source, evt.clone(), guard, effect),
+            }
+        }
+        Ok(())
+    }
+
+    /// Path of the state `target` points at from state `source`
+    fn resolve(&self, ids: &HashMap<String, String>, source: &str, parent: Option<&str>, target: &str) -> Result<String> {
+        if target.starts_with('#') {
+            let reference = &target[1..];
+            // `#id` or `#id.child.path`
+            let (id, rest) = match reference.find('.') {
+                Some(x) => (&reference[..x], &reference[x..]),
+                None    => (reference, "")
+            };
+            return match ids.get(id) {
+                Some(path) if path.is_empty() => Ok(rest.trim_start_matches('.').to_string()),
+                Some(path)                    => Ok(format!("{}{}", path, rest)),
+                None                          => Err(self.error(source, format!("no state with id {:?}", id)))
+            }
+        }
+        if target.starts_with('.') {
+            return Ok(format!("{}{}", source, target))
+        }
+        Ok(match parent {
+            Some(p) => format!("{}.{}", p, target),
+            None    => target.to_string()
+        })
+    }
+}
+
+impl ModelReader for XStateReader {
+    fn read_states(&self) -> Result<HashMap<String, State>> {
+        let states = match self.json.find("states").and_then(|x| x.as_object()) {
+            Some(x) => x,
+            None    => return Err(HsmGenError::model(format!("{} is not an XState machine config, it has no states", self.file)))
+        };
+        let mut ids = HashMap::new();
+        if let Some(id) = self.json.find("id").and_then(|x| x.as_string()) {
+            ids.insert(id.to_string(), String::new());
+        }
+        for (key, node) in states.iter() {
+            index_ids(key, node, &mut ids);
+        }
+        let mut builder = ModelBuilder::new();
+        for (key, node) in states.iter() {
+            if let Err(e) = self.read_state(&mut builder, &ids, key, node, None) {
+                try!(self.diagnostics.recover(e));
+            }
+        }
+        builder.build(&self.diagnostics)
+    }
+
+    fn diagnostic_log(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+}
+
+/// Records the path of every state that has an `id`
+fn index_ids(path: &str, node: &Json, ids: &mut HashMap<String, String>) {
+    if let Some(id) = node.find("id").and_then(|x| x.as_string()) {
+        ids.insert(id.to_string(), path.to_string());
+    }
+    if let Some(children) = node.find("states").and_then(|x| x.as_object()) {
+        for (key, child) in children.iter() {
+            index_ids(&format!("{}.{}", path, key), child, ids);
+        }
+    }
+}
+
+/// The name of a guard or action, `"name"` or `{"type": "name"}`
+fn name(json: &Json) -> Option<String> {
+    match *json {
+        Json::String(ref x) => Some(x.clone()),
+        Json::Object(_)     => json.find("type").and_then(|x| x.as_string()).map(|x| x.to_string()),
+        _                   => None
+    }
+}
+
+/// One action or an array of them, merged into one block
+fn actions(json: Option<&Json>) -> Option<String> {
+    match json {
+        Some(&Json::Array(ref arr)) => join_code(arr.iter().filter_map(name).collect()),
+        Some(x)                     => name(x),
+        None                        => None
+    }
+}
+
+fn event_name(evt: &Event) -> String {
+    match *evt {
+        Event::Signal { ref name, .. } | Event::Time { ref name, .. } => name.clone(),
+        Event::Change { ref id, .. }                                => id.clone(),
+        _                                                           => "any".to_string()
+    }
+}
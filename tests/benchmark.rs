@@ -0,0 +1,72 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+extern crate hsm_gen;
+
+mod common;
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use hsm_gen::{XmiReader, ModelReader, State};
+
+use common::{state, actions, guarded, target};
+
+
+/// The fastest of three reads of `file`, in seconds, and the states read
+fn read(file: &str) -> (f64, HashMap<String, State>) {
+    let reader = XmiReader::from_file(file).unwrap();
+    let mut fastest = None;
+    let mut states = HashMap::new();
+    for _ in 0..3 {
+        let start = Instant::now();
+        states = reader.read_states().unwrap();
+        let elapsed = start.elapsed();
+        let secs = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 * 1e-9;
+        fastest = Some(fastest.map_or(secs, |x: f64| x.min(secs)));
+    }
+    assert!(reader.diagnostics().is_empty(), "{:?}", reader.diagnostics());
+    (fastest.unwrap(), states)
+}
+
+/// Reads the machine with 40 composite states of 20 substates each, and the
+/// one with the first 20 of them. Reading should take time in proportion to
+/// the size of the model, about twice as long for the whole one, and four
+/// times as long would mean lookups that search the model. The times are
+/// printed, run with `--nocapture` to see them.
+#[test]
+fn read_840_states() {
+    let (half, half_states) = read("tests/models/papyrus/benchmark-half.uml");
+    let (full, states) = read("tests/models/papyrus/benchmark.uml");
+    println!("read {} states in {:.3} s, {} states in {:.3} s", half_states.len(), half, states.len(), full);
+    assert_eq!(half_states.len(), 420);
+    assert!(full < half * 3.0, "reading 840 states took {:.3} s, 420 states {:.3} s", full, half);
+
+    assert_eq!(states.len(), 840);
+    assert_eq!(state(&states, "Group39").parent, None);
+    assert_eq!(state(&states, "Group39State19").parent, Some("Group39".to_string()));
+    assert_eq!(target(guarded(actions(state(&states, "Group0State0"), "Next"), None)), Some("Group0State1"));
+    assert_eq!(target(guarded(actions(state(&states, "Group0State19"), "Next"), None)), Some("Group0State0"));
+    // The last group skips back to the initial state of the first
+    assert_eq!(target(guarded(actions(state(&states, "Group39"), "Skip"), None)), Some("Group0State0"));
+}
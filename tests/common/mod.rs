@@ -0,0 +1,135 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+
+use hsm_gen::{State, Event, CondAction, Action};
+
+
+/// The state named `name`
+pub fn state<'a>(states: &'a HashMap<String, State>, name: &str) -> &'a State {
+    match states.get(name) {
+        Some(state) => state,
+        None        => panic!("no state {} in {:?}", name, names(states))
+    }
+}
+
+/// The names of the states, sorted
+pub fn names(states: &HashMap<String, State>) -> Vec<&str> {
+    let mut names = states.keys().map(|x| x.as_str()).collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+/// The name of a signal or time event, `when(condition)` for a change event
+pub fn event_name(event: &Event) -> String {
+    match *event {
+        Event::Signal {ref name, ..} | Event::Time {ref name, ..} => name.clone(),
+        Event::Change {ref condition, ..}                         => format!("when({})", condition),
+        Event::UserAny                                            => "_".to_string(),
+        Event::Any                                                => "*".to_string(),
+    }
+}
+
+/// The event named `name` that `state` has actions for
+pub fn event<'a>(state: &'a State, name: &str) -> &'a Event {
+    match state.actions.keys().find(|x| event_name(x) == name) {
+        Some(event) => event,
+        None        => panic!("{} has no actions for {}, only for {:?}",
+                              state.name, name, state.actions.keys().map(event_name).collect::<Vec<_>>())
+    }
+}
+
+/// The actions of `state` on the event named `name`
+pub fn actions<'a>(state: &'a State, name: &str) -> &'a [CondAction] {
+    &state.actions[event(state, name)]
+}
+
+/// The action of `cas` with the guard `guard`
+pub fn guarded<'a>(cas: &'a [CondAction], guard: Option<&str>) -> &'a CondAction {
+    match cas.iter().find(|x| x.guard.as_ref().map(|x| x.as_str()) == guard) {
+        Some(ca) => ca,
+        None     => panic!("no action with guard {:?} in {:?}", guard, cas)
+    }
+}
+
+/// The state `ca` transitions to, None when it stays
+pub fn target(ca: &CondAction) -> Option<&str> {
+    match ca.action {
+        Action::Transition {ref state, ..} => Some(state.as_str()),
+        Action::Ignore | Action::Parent    => None,
+        Action::Diverge {..}               => panic!("{:?} leads to a choice", ca),
+    }
+}
+
+/// The branches of the choice `ca` leads to
+pub fn branches(ca: &CondAction) -> &[CondAction] {
+    match ca.action {
+        Action::Diverge {ref cond_act_vec, ..} => cond_act_vec,
+        _                                      => panic!("{:?} does not lead to a choice", ca),
+    }
+}
+
+/// The timeout of the time event `name` of `state`
+pub fn timeout_ms(state: &State, name: &str) -> u32 {
+    match *event(state, name) {
+        Event::Time {timeout_ms, ..} => timeout_ms,
+        ref x                        => panic!("{:?} is not a time event", x),
+    }
+}
+
+/// Checks the door most examples model: Closed with the substates Unlocked and
+/// Locked, a choice on Lock and a time event `autoclose` that closes the door
+/// after 30 seconds. `lock_guard` is the guard of Lock.
+pub fn assert_door(states: &HashMap<String, State>, lock_guard: &str, autoclose: &str) {
+    assert_eq!(names(states), vec!("Closed", "Locked", "Opened", "Unlocked"));
+    assert_eq!(state(states, "Closed").parent, None);
+    assert_eq!(state(states, "Opened").parent, None);
+    assert_eq!(state(states, "Unlocked").parent, Some("Closed".to_string()));
+    assert_eq!(state(states, "Locked").parent, Some("Closed".to_string()));
+
+    let locked = state(states, "Locked");
+    assert_eq!(locked.entry, Some("shr.engage_bolt()".to_string()));
+    assert_eq!(locked.exit, Some("shr.release_bolt()".to_string()));
+    assert_eq!(state(states, "Unlocked").entry, None);
+    assert_eq!(target(guarded(actions(locked, "Unlock"), None)), Some("Unlocked"));
+
+    let closed = state(states, "Closed");
+    assert_eq!(target(guarded(actions(closed, "Open"), None)), Some("Opened"));
+
+    // Transitions to Closed end in its initial state
+    let opened = state(states, "Opened");
+    let close = guarded(actions(opened, "Close"), None);
+    assert_eq!(close.effect, Some("shr.beep()".to_string()));
+    assert_eq!(target(close), Some("Unlocked"));
+    assert_eq!(timeout_ms(opened, autoclose), 30000);
+    assert_eq!(target(guarded(actions(opened, autoclose), None)), Some("Unlocked"));
+
+    let unlocked = state(states, "Unlocked");
+    let choice = branches(guarded(actions(unlocked, "Lock"), Some(lock_guard)));
+    assert_eq!(choice.len(), 2);
+    assert_eq!(target(guarded(choice, Some("shr.bolt_free"))), Some("Locked"));
+    assert_eq!(target(guarded(choice, Some("else"))), Some("Unlocked"));
+}
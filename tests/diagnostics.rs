@@ -0,0 +1,127 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+extern crate hsm_gen;
+
+mod common;
+
+use hsm_gen::{XmiReader, ModelReader, Severity, ErrorKind};
+
+use common::{names, state, actions, guarded, target};
+
+
+/// A machine with a state without name and a transition without trigger
+const BROKEN: &'static str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<uml:Model xmi:version="20131001" xmlns:xmi="http://www.omg.org/spec/XMI/20131001" xmlns:uml="http://www.eclipse.org/uml2/5.0.0/UML" xmi:id="_model" name="broken">
+  <packagedElement xmi:type="uml:StateMachine" xmi:id="_sm" name="Broken">
+    <region xmi:id="_region" name="Region1">
+      <transition xmi:id="_t_init" source="_init" target="_idle"/>
+      <transition xmi:id="_t_go" source="_idle" target="_nameless"/>
+      <transition xmi:id="_t_back" source="_nameless" target="_idle">
+        <trigger xmi:id="_trig_back" event="_ev_back"/>
+      </transition>
+      <subvertex xmi:type="uml:Pseudostate" xmi:id="_init"/>
+      <subvertex xmi:type="uml:State" xmi:id="_idle" name="Idle"/>
+      <subvertex xmi:type="uml:State" xmi:id="_nameless"/>
+    </region>
+  </packagedElement>
+  <packagedElement xmi:type="uml:SignalEvent" xmi:id="_ev_back" name="Back"/>
+</uml:Model>
+"#;
+
+/// A machine with a guard on the transition out of a junction
+const GUARDED_JUNCTION: &'static str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<uml:Model xmi:version="20131001" xmlns:xmi="http://www.omg.org/spec/XMI/20131001" xmlns:uml="http://www.eclipse.org/uml2/5.0.0/UML" xmi:id="_model" name="junction">
+  <packagedElement xmi:type="uml:StateMachine" xmi:id="_sm" name="Lamp">
+    <region xmi:id="_region" name="Region1">
+      <transition xmi:id="_t_init" source="_init" target="_off"/>
+      <transition xmi:id="_t_switch" source="_off" target="_junction">
+        <trigger xmi:id="_trig_switch" event="_ev_switch"/>
+      </transition>
+      <transition xmi:id="_t_on" source="_junction" target="_on" guard="_t_on_guard">
+        <ownedRule xmi:id="_t_on_guard" name="guard">
+          <specification xmi:type="uml:LiteralString" xmi:id="_t_on_guard_spec" value="shr.powered"/>
+        </ownedRule>
+      </transition>
+      <subvertex xmi:type="uml:Pseudostate" xmi:id="_init"/>
+      <subvertex xmi:type="uml:Pseudostate" xmi:id="_junction" kind="junction"/>
+      <subvertex xmi:type="uml:State" xmi:id="_off" name="Off"/>
+      <subvertex xmi:type="uml:State" xmi:id="_on" name="On"/>
+    </region>
+  </packagedElement>
+  <packagedElement xmi:type="uml:SignalEvent" xmi:id="_ev_switch" name="Switch"/>
+</uml:Model>
+"#;
+
+
+#[test]
+fn first_error_stops_reading() {
+    let reader = XmiReader::from_str(BROKEN).unwrap();
+    let err = reader.read_states().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Model);
+    assert!(reader.diagnostics().is_empty(), "{:?}", reader.diagnostics());
+}
+
+#[test]
+fn collect_all() {
+    let mut reader = XmiReader::from_str(BROKEN).unwrap();
+    reader.set_collect_all(true);
+    let states = reader.read_states().unwrap();
+    // The state without name is named by its id
+    assert_eq!(names(&states), vec!("Idle", "_nameless"));
+
+    let diagnostics = reader.diagnostics();
+    assert!(diagnostics.iter().all(|x| x.severity == Severity::Error), "{:?}", diagnostics);
+    let nameless = diagnostics.iter().find(|x| x.error.message.contains("missing attribute \"name\""))
+                                     .expect("no diagnostic for the state without name");
+    assert_eq!(nameless.error.id, Some("_nameless".to_string()));
+    assert_eq!(nameless.error.location.as_ref().map(|x| x.line), Some(12));
+    let untriggered = diagnostics.iter().find(|x| x.error.message.contains("without a trigger"))
+                                        .expect("no diagnostic for the transition without trigger");
+    assert_eq!(untriggered.error.id, Some("_t_go".to_string()));
+    assert_eq!(untriggered.error.location.as_ref().map(|x| x.line), Some(6));
+}
+
+#[test]
+fn collect_all_of_a_valid_model() {
+    let mut reader = XmiReader::from_file("tests/models/papyrus/door.uml").unwrap();
+    reader.set_collect_all(true);
+    assert_eq!(reader.read_states().unwrap().len(), 4);
+    assert!(reader.diagnostics().is_empty(), "{:?}", reader.diagnostics());
+}
+
+#[test]
+fn guarded_junction() {
+    let reader = XmiReader::from_str(GUARDED_JUNCTION).unwrap();
+    let err = reader.read_states().unwrap_err();
+    assert!(err.message.contains("a transition out of a junction cannot have a guard"), "{}", err);
+    assert_eq!(err.id, Some("_t_on".to_string()));
+    assert_eq!(err.location.as_ref().map(|x| x.line), Some(9));
+
+    let mut reader = XmiReader::from_str(GUARDED_JUNCTION).unwrap();
+    reader.set_collect_all(true);
+    let states = reader.read_states().unwrap();
+    // Read without the guard
+    assert_eq!(target(guarded(actions(state(&states, "Off"), "Switch"), None)), Some("On"));
+    assert_eq!(reader.diagnostics().len(), 1, "{:?}", reader.diagnostics());
+}
@@ -0,0 +1,55 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+extern crate hsm_gen;
+
+mod common;
+
+use hsm_gen::{DrawioReader, ModelReader};
+
+use common::assert_door;
+
+
+#[test]
+fn door() {
+    let reader = DrawioReader::from_file("tests/models/drawio/door.drawio").unwrap();
+    let states = reader.read_states().unwrap();
+    assert!(reader.diagnostics().is_empty(), "{:?}", reader.diagnostics());
+    assert_door(&states, "shr.has_key", "OpenedAfter30000ms");
+}
+
+#[test]
+fn compressed() {
+    let reader = DrawioReader::from_file("tests/models/drawio/door-compressed.drawio").unwrap();
+    let states = reader.read_states().unwrap();
+    assert!(reader.diagnostics().is_empty(), "{:?}", reader.diagnostics());
+    assert_door(&states, "shr.has_key", "OpenedAfter30000ms");
+}
+
+#[test]
+fn labelled_initial_transition() {
+    let reader = DrawioReader::from_file("tests/models/drawio/initial-label.drawio").unwrap();
+    let err = reader.read_states().unwrap_err();
+    assert!(err.message.contains("a transition out of an initial state cannot have a trigger"), "{}", err);
+    assert_eq!(err.location.as_ref().map(|x| x.line), Some(22));
+}
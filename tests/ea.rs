@@ -0,0 +1,55 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+extern crate hsm_gen;
+
+mod common;
+
+use hsm_gen::{XmiReader, ModelReader, Dialect};
+
+use common::assert_door;
+
+
+#[test]
+fn door() {
+    let reader = XmiReader::from_file("tests/models/ea/door.xml").unwrap();
+    assert_eq!(reader.dialect(), Dialect::EnterpriseArchitect);
+    // The guard is escaped twice in the export
+    assert_door(&reader.read_states().unwrap(), "shr.has_key && !shr.jammed", "AutoClose");
+}
+
+#[test]
+fn reference_to_an_unknown_trigger() {
+    let model = include_str!("models/ea/door.xml").replace("<trigger xmi:idref=\"EAID_TRIG_OPEN\"/>", "<trigger xmi:idref=\"EAID_TRIG_MISSING\"/>");
+    let reader = XmiReader::from_str(&model).unwrap();
+    assert_eq!(reader.dialect(), Dialect::EnterpriseArchitect);
+    let err = reader.read_states().unwrap_err();
+    assert_eq!(err.message, "trigger reference to unknown trigger EAID_TRIG_MISSING");
+
+    let mut reader = XmiReader::from_str(&model).unwrap();
+    reader.set_collect_all(true);
+    reader.read_states().unwrap();
+    let diagnostics = reader.diagnostics();
+    assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+    assert_eq!(diagnostics[0].error.message, "trigger reference to unknown trigger EAID_TRIG_MISSING");
+}
@@ -0,0 +1,62 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+extern crate hsm_gen;
+
+use hsm_gen::{XmiReader, ModelReader};
+
+
+const MODEL: &'static str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<uml:Model xmi:version="20131001" xmlns:xmi="http://www.omg.org/spec/XMI/20131001" xmlns:uml="http://www.eclipse.org/uml2/5.0.0/UML" xmi:id="_model" name="log">
+  <packagedElement xmi:type="uml:StateMachine" xmi:id="_sm" name="Log">
+    <region xmi:id="_region" name="Region1">
+      <transition xmi:id="_t_init" source="_init" target="_idle"/>
+      <transition xmi:id="_t_print" source="_idle" target="_idle">
+        <effect xmi:type="uml:OpaqueBehavior" xmi:id="_t_print_effect">
+          <body>shr.print("&amp;lt;br&amp;gt;")</body>
+        </effect>
+        <trigger xmi:id="_trig_print" event="_ev_print"/>
+      </transition>
+      <subvertex xmi:type="uml:Pseudostate" xmi:id="_init"/>
+      <subvertex xmi:type="uml:State" xmi:id="_idle" name="Idle"/>
+    </region>
+  </packagedElement>
+  <packagedElement xmi:type="uml:SignalEvent" xmi:id="_ev_print" name="Print"/>
+</uml:Model>
+"#;
+
+#[test]
+fn generic_bodies_are_decoded_once() {
+    let states = XmiReader::from_str(MODEL).unwrap().read_states().unwrap();
+    let idle = states.values().find(|x| x.name == "Idle").unwrap();
+    let actions = format!("{:?}", idle.actions);
+    assert!(actions.contains("&lt;br&gt;"), "{}", actions);
+}
+
+#[test]
+fn enterprise_architect_bodies_are_decoded_twice() {
+    let states = XmiReader::from_file("tests/models/ea/door.xml").unwrap().read_states().unwrap();
+    let unlocked = states.values().find(|x| x.name == "Unlocked").unwrap();
+    let actions = format!("{:?}", unlocked.actions);
+    assert!(actions.contains("shr.has_key && !shr.jammed"), "{}", actions);
+}
@@ -0,0 +1,132 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+extern crate hsm_gen;
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+
+use hsm_gen::{XmiReader, ModelReader, HsmGenerator, Event};
+
+
+/// The code generated for the model `file`, printed to `name` in the
+/// temporary directory
+fn generate(file: &str, name: &str) -> String {
+    let reader = XmiReader::from_file(file).unwrap();
+    let states = reader.read_states().unwrap();
+    let mut generator = HsmGenerator::new(true);
+    generator.set_hints(reader.machine_hints(None).unwrap());
+    generator.create_event_enum(&states).unwrap();
+    generator.create_hsm_objects(&states).unwrap();
+    generator.create_state_parent_impls(&states).unwrap();
+    generator.create_state_impls(&states).unwrap();
+    let output = env::temp_dir().join(name);
+    let output = output.to_str().unwrap();
+    generator.print(output).unwrap();
+    let mut code = String::new();
+    File::open(output).unwrap().read_to_string(&mut code).unwrap();
+    code
+}
+
+/// The code from `start` up to the end of its item
+fn item<'a>(code: &'a str, start: &str) -> &'a str {
+    let begin = code.find(start).unwrap_or_else(|| panic!("no {:?} in\n{}", start, code));
+    let rest = &code[begin..];
+    &rest[..rest.find("\n}").map_or(rest.len(), |x| x + 2)]
+}
+
+#[test]
+fn state_docs_are_on_the_struct() {
+    let code = generate("tests/models/papyrus/signals.uml", "hsm_gen_state_docs.rs");
+    let objects = item(&code, "hsm_define_objects!");
+    assert!(objects.contains("#[doc = \" The heater is on.\"] #[doc = \"\"] #[doc = \" It stays on until the thermostat is stopped.\"] Heating"), "{}", objects);
+    let heating = item(&code, "impl hsm::State<Events, States, SharedData> for Heating");
+    assert!(!heating.contains("///") && !heating.contains("#[doc"), "{}", heating);
+}
+
+#[test]
+fn transition_docs_are_comments_on_their_arm() {
+    let code = generate("tests/models/papyrus/signals.uml", "hsm_gen_transition_docs.rs");
+    let idle = item(&code, "impl hsm::State<Events, States, SharedData> for Idle");
+    assert!(idle.contains("// Starts heating on the first reading.\n            hsm::Event::User(Events::Measured"), "{}", idle);
+}
+
+#[test]
+fn signals_of_do_activities_have_their_attributes() {
+    let code = generate("tests/models/papyrus/signals.uml", "hsm_gen_do_activity.rs");
+    let events = item(&code, "pub enum Events");
+    assert!(events.contains("/// A new room temperature reading.\n    Measured(f64),"), "{}", events);
+    let heating = item(&code, "impl hsm::State<Events, States, SharedData> for Heating");
+    assert!(heating.contains("hsm::Event::User(Events::Measured(ref celsius)) => {\n                shr.regulate(*celsius);"), "{}", heating);
+}
+
+#[test]
+fn change_events_after_handled_events_and_on_poll() {
+    let code = generate("tests/models/papyrus/change.uml", "hsm_gen_change.rs");
+    let filling = item(&code, "impl hsm::State<Events, States, SharedData> for Filling");
+    // Checked after an event the state handles itself
+    assert!(filling.contains("if let hsm::Action::Ignore = action"), "{}", filling);
+    assert!(filling.contains("shr.level() >= shr.capacity()"), "{}", filling);
+    // Events passed to the parent are not, Poll covers those
+    assert!(!filling.contains("if let hsm::Action::Parent = action"), "{}", filling);
+    let poll = item(filling, "hsm::Event::User(Events::Poll) =>");
+    assert!(poll.contains("shr.level() >= shr.capacity()") && poll.contains("hsm::Action::Parent"), "{}", poll);
+}
+
+#[test]
+fn cfg_states_gate_their_impl_and_the_transitions_to_them() {
+    let code = generate("tests/models/papyrus/hints.uml", "hsm_gen_cfg.rs");
+    let off = item(&code, "impl hsm::State<Events, States, SharedData> for Off");
+    assert!(off.contains("#[cfg(feature = \"calibration\")]\n"), "{}", off);
+    assert!(code.contains("#[cfg(feature = \"calibration\")]\nimpl hsm::State<Events, States, SharedData> for Calibrating"), "{}", code);
+    let fallback = item(&code, "#[cfg(not(feature = \"calibration\"))]\nimpl hsm::State<Events, States, SharedData> for Calibrating");
+    assert!(fallback.contains("hsm::Action::Parent"), "{}", fallback);
+}
+
+#[test]
+fn cfg_states_with_substates_are_rejected() {
+    let reader = XmiReader::from_file("tests/models/papyrus/door.uml").unwrap();
+    let mut states = reader.read_states().unwrap();
+    for state in states.values_mut().filter(|x| x.name == "Closed") {
+        state.cfg = Some("feature = \"locking\"".to_string());
+    }
+    let mut generator = HsmGenerator::new(true);
+    let err = generator.create_state_impls(&states).unwrap_err();
+    assert!(err.message.contains("only states without substates can be gated"), "{}", err.message);
+}
+
+#[test]
+fn signals_named_like_generated_events_are_rejected() {
+    let reader = XmiReader::from_file("tests/models/papyrus/door.uml").unwrap();
+    let mut states = reader.read_states().unwrap();
+    for state in states.values_mut() {
+        state.actions = state.actions.drain().map(|(evt, cas)| match evt {
+            Event::Signal {ref name, ..} if name == "Open" => (Event::signal("Poll"), cas),
+            evt                                           => (evt, cas),
+        }).collect();
+    }
+    let mut generator = HsmGenerator::new(true);
+    let err = generator.create_event_enum(&states).unwrap_err();
+    assert!(err.message.contains("signal Poll has the name of a generated event"), "{}", err.message);
+}
@@ -0,0 +1,77 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+extern crate hsm_gen;
+
+mod common;
+
+use hsm_gen::{XmiReader, ScxmlReader, ModelReader, SourceLocation};
+
+use common::state;
+
+
+fn line_col(location: &Option<SourceLocation>) -> Option<(usize, usize)> {
+    location.as_ref().map(|x| (x.line, x.column))
+}
+
+#[test]
+fn xmi_states() {
+    let reader = XmiReader::from_file("tests/models/papyrus/door.uml").unwrap();
+    let states = reader.read_states().unwrap();
+    let closed = state(&states, "Closed");
+    assert_eq!(closed.location.as_ref().map(|x| x.file.as_str()), Some("tests/models/papyrus/door.uml"));
+    assert_eq!(line_col(&closed.location), Some((20, 7)));
+    assert_eq!(line_col(&state(&states, "Locked").location), Some((48, 11)));
+}
+
+#[test]
+fn scxml_states() {
+    let reader = ScxmlReader::from_file("tests/models/scxml/door.scxml").unwrap();
+    let states = reader.read_states().unwrap();
+    let closed = state(&states, "Closed");
+    assert_eq!(closed.location.as_ref().map(|x| x.file.as_str()), Some("tests/models/scxml/door.scxml"));
+    assert_eq!(line_col(&closed.location), Some((3, 3)));
+    assert_eq!(line_col(&state(&states, "Locked").location), Some((11, 5)));
+}
+
+#[test]
+fn model_error() {
+    let reader = XmiReader::from_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+<uml:Model xmi:version="20131001" xmlns:xmi="http://www.omg.org/spec/XMI/20131001" xmlns:uml="http://www.eclipse.org/uml2/5.0.0/UML" xmi:id="_model" name="m">
+  <packagedElement xmi:type="uml:StateMachine" xmi:id="_sm" name="M">
+    <region xmi:id="_region" name="Region1">
+      <transition xmi:id="_t_init" source="_init" target="_idle"/>
+      <transition xmi:id="_t_loop" source="_idle" target="_idle"/>
+      <subvertex xmi:type="uml:Pseudostate" xmi:id="_init"/>
+      <subvertex xmi:type="uml:State" xmi:id="_idle" name="Idle"/>
+    </region>
+  </packagedElement>
+</uml:Model>
+"#).unwrap();
+    let err = reader.read_states().unwrap_err();
+    let location = err.location.as_ref().expect("error without location");
+    assert_eq!(location.file, "<string>");
+    assert_eq!((location.line, location.column), (6, 7));
+    // The location leads the message
+    assert!(err.to_string().starts_with("<string>:6:7: "), "{}", err);
+}
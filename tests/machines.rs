@@ -0,0 +1,64 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+extern crate hsm_gen;
+
+mod common;
+
+use hsm_gen::{XmiReader, ModelReader};
+
+use common::{state, names, actions, guarded, target};
+
+
+#[test]
+fn list() {
+    let reader = XmiReader::from_file("tests/models/papyrus/machines.uml").unwrap();
+    assert_eq!(reader.state_machines().unwrap(),
+               vec!("machines::Alarm", "machines::Door::Behavior", "machines::Lamp::Behavior"));
+}
+
+#[test]
+fn read_by_qualified_name() {
+    let reader = XmiReader::from_file("tests/models/papyrus/machines.uml").unwrap();
+    let states = reader.read_machine("machines::Lamp::Behavior").unwrap();
+    assert_eq!(names(&states), vec!("Off", "On"));
+    assert_eq!(target(guarded(actions(state(&states, "Off"), "Switch"), None)), Some("On"));
+    assert_eq!(target(guarded(actions(state(&states, "On"), "Switch"), None)), Some("Off"));
+}
+
+#[test]
+fn read_by_unique_name() {
+    let reader = XmiReader::from_file("tests/models/papyrus/machines.uml").unwrap();
+    let states = reader.read_machine("Alarm").unwrap();
+    assert_eq!(names(&states), vec!("Idle", "Ringing"));
+    assert_eq!(target(guarded(actions(state(&states, "Idle"), "Open"), None)), Some("Ringing"));
+}
+
+#[test]
+fn ambiguous_name() {
+    let reader = XmiReader::from_file("tests/models/papyrus/machines.uml").unwrap();
+    assert!(reader.read_machine("Behavior").is_err());
+    assert!(reader.read_machine("Garage").is_err());
+    // Several machines and none picked
+    assert!(reader.read_states().is_err());
+}
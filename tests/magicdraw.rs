@@ -0,0 +1,53 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+extern crate hsm_gen;
+
+mod common;
+
+use hsm_gen::{XmiReader, ModelReader, Dialect};
+
+use common::assert_door;
+
+
+#[test]
+fn door() {
+    let reader = XmiReader::from_file("tests/models/magicdraw/door.xml").unwrap();
+    assert_eq!(reader.dialect(), Dialect::MagicDraw);
+    assert_door(&reader.read_states().unwrap(), "shr.has_key", "AutoClose");
+}
+
+#[test]
+fn reference_to_an_unknown_behavior() {
+    let model = include_str!("models/magicdraw/door.xml").replace("<entry xmi:idref=\"_lock\"/>", "<entry xmi:idref=\"_missing\"/>");
+    let reader = XmiReader::from_str(&model).unwrap();
+    assert_eq!(reader.dialect(), Dialect::MagicDraw);
+    let err = reader.read_states().unwrap_err();
+    assert_eq!(err.message, "reference to unknown behavior _missing");
+
+    let mut reader = XmiReader::from_str(&model).unwrap();
+    reader.set_collect_all(true);
+    reader.read_states().unwrap();
+    let diagnostics = reader.diagnostics();
+    assert!(diagnostics.iter().any(|x| x.error.message == "reference to unknown behavior _missing"), "{:?}", diagnostics);
+}
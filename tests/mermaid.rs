@@ -0,0 +1,53 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+extern crate hsm_gen;
+
+mod common;
+
+use hsm_gen::{MermaidReader, ModelReader};
+
+use common::assert_door;
+
+
+#[test]
+fn door() {
+    let reader = MermaidReader::from_file("tests/models/mermaid/door.mmd").unwrap();
+    let states = reader.read_states().unwrap();
+    assert!(reader.diagnostics().is_empty(), "{:?}", reader.diagnostics());
+    assert_door(&states, "shr.has_key", "OpenedAfter30000ms");
+}
+
+#[test]
+fn door_in_markdown() {
+    let reader = MermaidReader::from_markdown("tests/models/mermaid/door.md").unwrap();
+    assert_door(&reader.read_states().unwrap(), "shr.has_key", "OpenedAfter30000ms");
+}
+
+#[test]
+fn fork_with_a_guard() {
+    let reader = MermaidReader::from_file("tests/models/mermaid/fork.mmd").unwrap();
+    let err = reader.read_states().unwrap_err();
+    assert!(err.message.contains("a transition out of a junction cannot have a guard"), "{}", err);
+    assert_eq!(err.location.as_ref().map(|x| x.line), Some(6));
+}
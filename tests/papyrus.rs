@@ -0,0 +1,104 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+extern crate hsm_gen;
+
+mod common;
+
+use std::collections::HashMap;
+use std::fs::File;
+
+use hsm_gen::{XmiReader, ModelReader, Dialect, Severity, State};
+
+use common::{assert_door, state};
+
+
+#[test]
+fn door() {
+    let reader = XmiReader::from_file("tests/models/papyrus/door.uml").unwrap();
+    assert_eq!(reader.dialect(), Dialect::Generic);
+    assert_door(&reader.read_states().unwrap(), "shr.has_key", "AutoClose");
+}
+
+#[test]
+fn sidecar_files_read_the_model() {
+    for file in &["tests/models/papyrus/door.di", "tests/models/papyrus/door.notation"] {
+        let reader = XmiReader::from_file(file).unwrap();
+        assert_door(&reader.read_states().unwrap(), "shr.has_key", "AutoClose");
+    }
+}
+
+#[test]
+fn namespace_prefixes_are_resolved() {
+    let model = include_str!("models/papyrus/door.uml")
+        .replace("xmlns:uml=", "xmlns:u=")
+        .replace("uml:", "u:");
+    let reader = XmiReader::from_str(&model).unwrap();
+    assert_door(&reader.read_states().unwrap(), "shr.has_key", "AutoClose");
+}
+
+#[test]
+fn bodies_in_rust_or_else_the_first_one() {
+    let reader = XmiReader::from_file("tests/models/papyrus/bodies.uml").unwrap();
+    let states = reader.read_states().unwrap();
+    let entry = |name| state(&states, name).entry.clone().unwrap();
+    assert_eq!(entry("Rust"), "shr.start()");
+    assert_eq!(entry("NoRust"), "start(shr);");
+    assert_eq!(entry("MissingBody"), "start(shr);");
+    assert_eq!(entry("NoLanguage"), "shr.start()");
+    assert_eq!(entry("OneBody"), "shr.start()");
+
+    let mut warnings = reader.diagnostics().into_iter().map(|x| {
+        assert_eq!(x.severity, Severity::Warning);
+        (x.error.id.unwrap(), x.error.message)
+    }).collect::<Vec<_>>();
+    warnings.sort();
+    assert_eq!(warnings, vec!(
+        ("_missing_body_entry".to_string(), "2 languages for 1 bodies, no body for the Rust language, using the first one".to_string()),
+        ("_no_language_entry".to_string(), "2 bodies without a language, using the first one".to_string()),
+        ("_no_rust_entry".to_string(), "no Rust body, using the C one".to_string()),
+    ));
+}
+
+/// The states of `states` sorted, with their actions sorted by event, as text
+fn describe(states: &HashMap<String, State>) -> Vec<String> {
+    let mut names = states.keys().collect::<Vec<_>>();
+    names.sort();
+    names.into_iter().map(|name| {
+        let state = &states[name];
+        let mut actions = state.actions.iter().collect::<Vec<_>>();
+        actions.sort_by(|a, b| a.0.cmp(b.0));
+        format!("{} {:?} {:?} {:?} {:?} {:?}", name, state.parent, state.entry, state.exit, state.location, actions)
+    }).collect()
+}
+
+#[test]
+fn from_reader_reads_like_from_file() {
+    let file = "tests/models/papyrus/door.uml";
+    let from_file = XmiReader::from_file(file).unwrap().read_states().unwrap();
+    let from_reader = XmiReader::from_reader(File::open(file).unwrap()).unwrap().read_states().unwrap();
+    assert_door(&from_reader, "shr.has_key", "AutoClose");
+    // Locations in a model read from a stream name the input instead of a file
+    let expected = describe(&from_file).into_iter().map(|x| x.replace(file, "<input>")).collect::<Vec<_>>();
+    assert_eq!(describe(&from_reader), expected);
+}
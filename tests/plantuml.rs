@@ -0,0 +1,78 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+extern crate hsm_gen;
+
+mod common;
+
+use hsm_gen::{PlantUmlReader, ModelReader};
+
+use common::{assert_door, names, state, actions, guarded, target};
+
+
+#[test]
+fn door() {
+    let reader = PlantUmlReader::from_file("tests/models/plantuml/door.puml").unwrap();
+    let states = reader.read_states().unwrap();
+    assert!(reader.diagnostics().is_empty(), "{:?}", reader.diagnostics());
+    assert_door(&states, "shr.has_key", "OpenedAfter30000ms");
+}
+
+#[test]
+fn pseudostates_pass_on_the_event() {
+    let reader = PlantUmlReader::from_file("tests/models/plantuml/pseudostates.puml").unwrap();
+    let err = reader.read_states().unwrap_err();
+    assert!(err.message.contains("a transition out of an initial state cannot have a guard"), "{}", err);
+    assert_eq!(err.location.as_ref().map(|x| x.line), Some(3));
+
+    let mut reader = PlantUmlReader::from_file("tests/models/plantuml/pseudostates.puml").unwrap();
+    reader.set_collect_all(true);
+    reader.read_states().unwrap();
+    let lines = reader.diagnostics().iter().map(|x| x.error.location.as_ref().map(|x| x.line)).collect::<Vec<_>>();
+    assert_eq!(lines, vec!(Some(3), Some(6)));
+}
+
+#[test]
+fn floating_note_on_one_line() {
+    let reader = PlantUmlReader::from_file("tests/models/plantuml/floating-note.puml").unwrap();
+    let states = reader.read_states().unwrap();
+    assert!(reader.diagnostics().is_empty(), "{:?}", reader.diagnostics());
+    assert_eq!(names(&states), vec!("Off", "On"));
+    assert_eq!(target(guarded(actions(state(&states, "Off"), "Toggle"), None)), Some("On"));
+    assert_eq!(state(&states, "On").entry, Some("shr.light()".to_string()));
+}
+
+#[test]
+fn guarded_time_and_change_events() {
+    let reader = PlantUmlReader::from_file("tests/models/plantuml/guarded-after.puml").unwrap();
+    let states = reader.read_states().unwrap();
+    assert!(reader.diagnostics().is_empty(), "{:?}", reader.diagnostics());
+    // The triggers on different lines are one event each
+    let heating = state(&states, "Heating");
+    assert_eq!(heating.actions.len(), 2, "{:?}", heating.actions);
+    let after = actions(heating, "HeatingAfter5000ms");
+    assert_eq!(target(guarded(after, Some("shr.boiled()"))), Some("Done"));
+    assert_eq!(target(guarded(after, Some("else"))), Some("Failed"));
+    let full = actions(heating, "when(shr.full())");
+    assert_eq!(target(guarded(full, Some("else"))), Some("Heating"));
+}
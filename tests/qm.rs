@@ -0,0 +1,75 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+extern crate hsm_gen;
+
+mod common;
+
+use hsm_gen::{QmReader, ModelReader};
+
+use common::{state, names, actions, guarded, target, branches};
+
+
+#[test]
+fn door() {
+    let reader = QmReader::from_file("tests/models/qm/door.qm").unwrap();
+    let states = reader.read_states().unwrap();
+    assert!(reader.diagnostics().is_empty(), "{:?}", reader.diagnostics());
+    assert_eq!(names(&states), vec!("Closed", "Locked", "Opened", "Unlocked"));
+    assert_eq!(state(&states, "Unlocked").parent, Some("Closed".to_string()));
+    assert_eq!(state(&states, "Closed").parent, None);
+
+    let locked = state(&states, "Locked");
+    assert_eq!(locked.entry, Some("shr.engage_bolt()".to_string()));
+    assert_eq!(locked.exit, Some("shr.release_bolt()".to_string()));
+    assert_eq!(target(guarded(actions(locked, "Unlock"), None)), Some("Unlocked"));
+
+    assert_eq!(target(guarded(actions(state(&states, "Closed"), "Open"), None)), Some("Opened"));
+    let close = guarded(actions(state(&states, "Opened"), "Close"), None);
+    assert_eq!(close.effect, Some("shr.beep()".to_string()));
+    assert_eq!(target(close), Some("Unlocked"));
+
+    // The <choice> children of the Lock transition
+    let choice = branches(guarded(actions(state(&states, "Unlocked"), "Lock"), None));
+    assert_eq!(choice.len(), 2);
+    assert_eq!(target(guarded(choice, Some("shr.has_key && shr.bolt_free"))), Some("Locked"));
+    assert_eq!(target(guarded(choice, Some("else"))), Some("Unlocked"));
+}
+
+#[test]
+fn target_outside_the_model() {
+    let reader = QmReader::from_file("tests/models/qm/bad-target.qm").unwrap();
+    let err = reader.read_states().unwrap_err();
+    assert!(err.message.contains("target \"../../5\" does not lead to a state"), "{}", err);
+    // Transitions have no name, they are located at their state
+    assert_eq!(err.location.as_ref().map(|x| x.line), Some(8));
+
+    let mut reader = QmReader::from_file("tests/models/qm/bad-target.qm").unwrap();
+    reader.set_collect_all(true);
+    let states = reader.read_states().unwrap();
+    assert_eq!(names(&states), vec!("Off", "On"));
+    assert_eq!(target(guarded(actions(state(&states, "Off"), "Toggle"), None)), Some("On"));
+    let lines = reader.diagnostics().iter().map(|x| x.error.location.as_ref().map(|x| x.line)).collect::<Vec<_>>();
+    assert_eq!(lines, vec!(Some(8), Some(12)));
+    assert!(reader.diagnostics()[1].error.message.contains("history states are not supported"), "{:?}", reader.diagnostics());
+}
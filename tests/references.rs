@@ -0,0 +1,80 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+extern crate hsm_gen;
+
+mod common;
+
+use std::collections::HashMap;
+
+use hsm_gen::{XmiReader, ModelReader, State, Event, SignalAttribute};
+
+use common::{state, event};
+
+
+fn split_door() -> HashMap<String, State> {
+    let mut reader = XmiReader::from_file("tests/models/papyrus/split/door.uml").unwrap();
+    reader.add_search_path("tests/models/papyrus/library").unwrap();
+    reader.read_states().unwrap()
+}
+
+fn attributes(event: &Event) -> Vec<SignalAttribute> {
+    match *event {
+        Event::Signal {ref attributes, ..} => attributes.clone(),
+        _                                  => panic!("{:?} is not a signal event", event)
+    }
+}
+
+#[test]
+fn intra_document_references_of_other_documents() {
+    let states = split_door();
+    assert_eq!(attributes(event(state(&states, "Closed"), "Open")), vec!(SignalAttribute {name: "wide".to_string(), ty: "bool".to_string()}));
+}
+
+#[test]
+fn references_relative_to_the_referring_document() {
+    let states = split_door();
+    let tick = event(state(&states, "Opened"), "Tick");
+    assert_eq!(attributes(tick), vec!(SignalAttribute {name: "elapsed".to_string(), ty: "u64".to_string()}));
+    match *tick {
+        Event::Signal {location: Some(ref location), ..} => assert!(location.file.ends_with("timers.uml")),
+        _                                                => panic!("no location for {:?}", tick)
+    }
+}
+
+#[test]
+fn missing_documents_are_errors() {
+    let reader = XmiReader::from_file("tests/models/papyrus/split/door.uml").unwrap();
+    let err = reader.read_states().unwrap_err();
+    assert!(err.message.contains("timers.uml"), "{}", err.message);
+}
+
+#[test]
+fn only_reference_attributes_load_documents() {
+    let mut reader = XmiReader::from_file("tests/models/papyrus/split/annotated.uml").unwrap();
+    reader.add_search_path("tests/models/papyrus/library").unwrap();
+    let model = reader.model().unwrap();
+    let documents = model.references().into_iter().map(|(path, _)| path.to_string()).collect::<Vec<_>>();
+    assert_eq!(documents.len(), 1, "{:?}", documents);
+    assert!(documents[0].ends_with("signals.uml"), "{:?}", documents);
+}
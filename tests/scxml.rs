@@ -0,0 +1,83 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+extern crate hsm_gen;
+
+mod common;
+
+use hsm_gen::{ScxmlReader, ModelReader, Severity};
+
+use common::{state, names, actions, guarded, target};
+
+
+#[test]
+fn door() {
+    let reader = ScxmlReader::from_file("tests/models/scxml/door.scxml").unwrap();
+    let states = reader.read_states().unwrap();
+    assert!(reader.diagnostics().is_empty(), "{:?}", reader.diagnostics());
+    assert_eq!(names(&states), vec!("Closed", "Locked", "Opened", "Unlocked"));
+    assert_eq!(state(&states, "Locked").parent, Some("Closed".to_string()));
+    assert_eq!(state(&states, "Opened").parent, None);
+
+    let locked = state(&states, "Locked");
+    assert_eq!(locked.entry, Some("shr.engage_bolt()".to_string()));
+    assert_eq!(locked.exit, Some("shr.release_bolt()".to_string()));
+    assert_eq!(target(guarded(actions(locked, "Unlock"), None)), Some("Unlocked"));
+
+    let lock = guarded(actions(state(&states, "Unlocked"), "Lock"), Some("shr.has_key && shr.bolt_free"));
+    assert_eq!(target(lock), Some("Locked"));
+
+    // The <initial> of Closed leads to Unlocked
+    let opened = state(&states, "Opened");
+    let close = guarded(actions(opened, "Close"), None);
+    assert_eq!(close.effect, Some("shr.beep()".to_string()));
+    assert_eq!(target(close), Some("Unlocked"));
+
+    // A transition without target stays in the state
+    let knock = guarded(actions(opened, "Knock"), None);
+    assert_eq!(knock.effect, Some("shr.knock_back()".to_string()));
+    assert_eq!(target(knock), None);
+}
+
+#[test]
+fn unsupported_elements() {
+    let reader = ScxmlReader::from_file("tests/models/scxml/unsupported.scxml").unwrap();
+    let err = reader.read_states().unwrap_err();
+    assert!(err.message.contains("transitions without event are not supported"), "{}", err);
+    // Transitions have no id, they are located at their state
+    assert_eq!(err.location.as_ref().map(|x| x.line), Some(3));
+
+    let mut reader = ScxmlReader::from_file("tests/models/scxml/unsupported.scxml").unwrap();
+    reader.set_collect_all(true);
+    let states = reader.read_states().unwrap();
+    assert_eq!(names(&states), vec!("Busy", "Idle"));
+    assert_eq!(target(guarded(actions(state(&states, "Idle"), "Go"), None)), Some("Busy"));
+    let diagnostics = reader.diagnostics().into_iter().map(|x|
+        (x.severity, x.error.message, x.error.location.map(|x| x.line))
+    ).collect::<Vec<_>>();
+    assert_eq!(diagnostics, vec!(
+        (Severity::Error, "transitions without event are not supported".to_string(), Some(3)),
+        (Severity::Warning, "<send> is not supported and was ignored, use <script>".to_string(), Some(3)),
+        (Severity::Error, "<history> is not supported".to_string(), Some(10)),
+    ));
+}
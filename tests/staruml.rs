@@ -0,0 +1,63 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+extern crate hsm_gen;
+
+mod common;
+
+use hsm_gen::{MdjReader, ModelReader};
+
+use common::{assert_door, state, actions, guarded, target};
+
+
+#[test]
+fn door() {
+    let reader = MdjReader::from_file("tests/models/staruml/door.mdj").unwrap();
+    assert_door(&reader.read_states().unwrap(), "shr.has_key", "AutoClose");
+}
+
+#[test]
+fn read_through_the_model_reader_trait() {
+    let mut reader: Box<ModelReader> = Box::new(MdjReader::from_file("tests/models/staruml/door.mdj").unwrap());
+    reader.set_collect_all(true);
+    assert_door(&reader.read_states().unwrap(), "shr.has_key", "AutoClose");
+    assert!(reader.diagnostics().is_empty());
+}
+
+#[test]
+fn unreadable_events() {
+    let reader = MdjReader::from_file("tests/models/staruml/bad-events.mdj").unwrap();
+    let err = reader.read_states().unwrap_err();
+    assert!(err.message.contains("time event without a valid timeout: \"a while\""), "{}", err);
+    assert_eq!(err.id, Some("ev_off".to_string()));
+    assert_eq!(err.location.as_ref().map(|x| x.line), Some(36));
+
+    let mut reader = MdjReader::from_file("tests/models/staruml/bad-events.mdj").unwrap();
+    reader.set_collect_all(true);
+    let states = reader.read_states().unwrap();
+    assert_eq!(target(guarded(actions(state(&states, "Off"), "Toggle"), None)), Some("On"));
+    // Transitions with events that could not be read are left out
+    assert!(state(&states, "On").actions.is_empty(), "{:?}", state(&states, "On").actions);
+    let diagnostics = reader.diagnostics().into_iter().map(|x| (x.error.id, x.error.location.map(|x| x.line))).collect::<Vec<_>>();
+    assert_eq!(diagnostics, vec!((Some("ev_off".to_string()), Some(36)), (Some("ev_change".to_string()), Some(45))));
+}
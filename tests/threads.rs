@@ -0,0 +1,45 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+extern crate hsm_gen;
+
+use std::sync::Arc;
+use std::thread;
+
+use hsm_gen::XmiReader;
+
+
+#[test]
+fn one_reader_reads_on_several_threads() {
+    let reader = Arc::new(XmiReader::from_file("tests/models/papyrus/machines.uml").unwrap());
+    let threads: Vec<_> = vec!(("machines::Door::Behavior", "Opened"), ("machines::Lamp::Behavior", "On")).into_iter().map(|(machine, state)| {
+        let reader = reader.clone();
+        thread::spawn(move || {
+            let states = reader.read_machine(machine).unwrap();
+            assert!(states.values().any(|x| x.name == state), "no state {} in {}", state, machine);
+        })
+    }).collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+extern crate hsm_gen;
+
+mod common;
+
+use hsm_gen::{XStateReader, ModelReader};
+
+use common::{state, names, actions, guarded, target, timeout_ms};
+
+
+#[test]
+fn door() {
+    let reader = XStateReader::from_file("tests/models/xstate/door.json").unwrap();
+    let states = reader.read_states().unwrap();
+    assert!(reader.diagnostics().is_empty(), "{:?}", reader.diagnostics());
+    assert_eq!(names(&states), vec!("Closed", "Locked", "Opened", "Unlocked"));
+    assert_eq!(state(&states, "Unlocked").parent, Some("Closed".to_string()));
+    assert_eq!(state(&states, "Locked").parent, Some("Closed".to_string()));
+    assert_eq!(state(&states, "Opened").parent, None);
+
+    let locked = state(&states, "Locked");
+    assert_eq!(locked.entry, Some("shr.engage_bolt()".to_string()));
+    assert_eq!(locked.exit, Some("shr.release_bolt()".to_string()));
+    assert_eq!(target(guarded(actions(locked, "Unlock"), None)), Some("Unlocked"));
+
+    assert_eq!(target(guarded(actions(state(&states, "Closed"), "Open"), None)), Some("Opened"));
+
+    // An array of transitions is tried in order, the last one has no target
+    let lock = actions(state(&states, "Unlocked"), "Lock");
+    assert_eq!(lock.len(), 2);
+    assert_eq!(target(guarded(lock, Some("shr.has_key && shr.bolt_free"))), Some("Locked"));
+    let beep = guarded(lock, None);
+    assert_eq!(beep.effect, Some("shr.beep()".to_string()));
+    assert_eq!(target(beep), None);
+
+    let opened = state(&states, "Opened");
+    let close = guarded(actions(opened, "Close"), None);
+    assert_eq!(close.effect, Some("shr.beep()".to_string()));
+    assert_eq!(target(close), Some("Unlocked"));
+    assert_eq!(timeout_ms(opened, "OpenedAfter30000ms"), 30000);
+    assert_eq!(target(guarded(actions(opened, "OpenedAfter30000ms"), None)), Some("Unlocked"));
+}
+
+#[test]
+fn duplicate_names_and_unknown_targets() {
+    let reader = XStateReader::from_file("tests/models/xstate/duplicate.json").unwrap();
+    let err = reader.read_states().unwrap_err();
+    assert!(err.message.contains("target Stoped is not a state"), "{}", err);
+    assert_eq!(err.location.as_ref().map(|x| x.line), Some(17));
+
+    let mut reader = XStateReader::from_file("tests/models/xstate/duplicate.json").unwrap();
+    reader.set_collect_all(true);
+    let states = reader.read_states().unwrap();
+    assert_eq!(names(&states), vec!("Idle", "Playing", "Stopped"));
+    let diagnostics = reader.diagnostics();
+    assert_eq!(diagnostics.len(), 2, "{:?}", diagnostics);
+    let duplicate = &diagnostics[1].error;
+    assert!(duplicate.message.contains("state name Idle is also used by state Playing.Idle"), "{}", duplicate);
+    assert_eq!(duplicate.id, Some("Stopped.Idle".to_string()));
+    assert_eq!(duplicate.location.as_ref().map(|x| x.line), Some(11));
+}
+
+#[test]
+fn parallel_state_enters_the_region_written_first() {
+    let mut reader = XStateReader::from_file("tests/models/xstate/parallel.json").unwrap();
+    reader.set_collect_all(true);
+    let states = reader.read_states().unwrap();
+    let diagnostics = reader.diagnostics();
+    assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+    assert!(diagnostics[0].error.message.contains("parallel state has 2 regions"), "{:?}", diagnostics);
+    assert_eq!(diagnostics[0].error.location.as_ref().map(|x| x.line), Some(5));
+    let armed = state(&states, "Armed");
+    assert_eq!(armed.initial_transition.as_ref().map(|x| x.target_id.as_str()), Some("Armed.Watching"));
+}
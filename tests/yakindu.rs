@@ -0,0 +1,57 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+extern crate hsm_gen;
+
+mod common;
+
+use hsm_gen::{SctReader, ModelReader};
+
+use common::{assert_door, state, actions, guarded, target, branches};
+
+
+#[test]
+fn door() {
+    let reader = SctReader::from_file("tests/models/yakindu/door.sct").unwrap();
+    let states = reader.read_states().unwrap();
+    assert!(reader.diagnostics().is_empty(), "{:?}", reader.diagnostics());
+    assert_door(&states, "shr.has_key", "OpenedAfter30000ms");
+}
+
+#[test]
+fn default_out_of_a_state() {
+    let reader = SctReader::from_file("tests/models/yakindu/default-state.sct").unwrap();
+    let err = reader.read_states().unwrap_err();
+    assert!(err.message.contains("trigger \"default\" is only allowed out of a choice"), "{}", err);
+    assert_eq!(err.id, Some("_t_off".to_string()));
+    assert_eq!(err.location.as_ref().map(|x| x.line), Some(16));
+
+    // The default transition of the choice is read as its else branch
+    let mut reader = SctReader::from_file("tests/models/yakindu/default-state.sct").unwrap();
+    reader.set_collect_all(true);
+    let states = reader.read_states().unwrap();
+    let choice = branches(guarded(actions(state(&states, "Off"), "Toggle"), None));
+    assert_eq!(target(guarded(choice, Some("shr.powered"))), Some("On"));
+    assert_eq!(target(guarded(choice, Some("else"))), Some("Off"));
+    assert_eq!(reader.diagnostics().len(), 1, "{:?}", reader.diagnostics());
+}